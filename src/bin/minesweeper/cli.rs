@@ -0,0 +1,189 @@
+use crate::game::{validate_size, Difficulty};
+use crate::scores::PRESET_NAMES;
+use std::str::FromStr;
+use std::time::Duration;
+
+pub const USAGE: &str = "\
+Usage: minesweeper [OPTIONS]
+
+Options:
+  -h, --help                 Print this help and exit
+  -d, --difficulty <LEVEL>   beginner, intermediate or expert (default: beginner)
+      --width <N>            Board width of a custom board
+      --height <N>           Board height of a custom board
+      --mines <N>            Number of mines of a custom board
+      --seed <N>             Seed of the first board
+      --name <NAME>          Player name for the best time table (default: $USER)
+      --no-guess             Generate boards solvable without guessing
+      --resume               Resume the game saved on the last quit (done by default
+                             when no options for a new game are given)
+      --record <FILE>        Record the session into a replay file
+      --replay <FILE>        Play back a replay file
+      --replay-speed <X>     Initial playback speed of --replay (default: 1)
+      --telnet <ADDR>        Serve games over telnet on ADDR (e.g. 0.0.0.0:2323)
+      --max-clients <N>      Maximum number of simultaneous telnet clients (default: 16)
+      --idle-timeout <SECS>  Disconnect idle telnet clients after SECS seconds (default: 300)";
+
+pub enum Command {
+    Help,
+    Play(Options),
+}
+
+pub struct Options {
+    pub difficulty: Difficulty,
+    pub seed: Option<u64>,
+    pub no_guess: bool,
+    pub player_name: Option<String>,
+    pub resume: bool,
+    pub has_game_options: bool,
+    pub record: Option<String>,
+    pub replay: Option<String>,
+    pub replay_speed: f64,
+    pub telnet: Option<String>,
+    pub max_clients: usize,
+    pub idle_timeout: Duration,
+}
+
+fn parse_difficulty(value: &str) -> Result<Difficulty, String> {
+    PRESET_NAMES
+        .iter()
+        .position(|x| *x == value)
+        .or_else(|| value.parse().ok().filter(|&i| i < PRESET_NAMES.len()))
+        .map(Difficulty::Preset)
+        .ok_or_else(|| {
+            format!(
+                "invalid value '{}' for --difficulty (expected {})",
+                value,
+                PRESET_NAMES.join(", ")
+            )
+        })
+}
+
+fn parse_number<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {} (expected a number)", value, name))
+}
+
+pub fn parse_args(
+    args: impl IntoIterator<Item = String>,
+    terminal_size: Option<(usize, usize)>,
+) -> Result<Command, String> {
+    let mut args = args.into_iter();
+    let mut difficulty = None;
+    let mut seed = None;
+    let mut no_guess = false;
+    let mut player_name = None;
+    let mut resume = false;
+    let mut record = None;
+    let mut replay = None;
+    let mut replay_speed = None;
+    let mut telnet = None;
+    let mut max_clients = None;
+    let mut idle_timeout = None;
+    let (mut width, mut height, mut mines) = (None, None, None);
+
+    while let Some(arg) = args.next() {
+        // Accept both "--option value" and "--option=value"
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name.to_string(), Some(value)),
+            _ => (arg.clone(), None),
+        };
+        let mut value = |name: &str| {
+            inline_value
+                .map(str::to_string)
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("missing value for {}", name))
+        };
+
+        match name.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-d" | "--difficulty" => difficulty = Some(parse_difficulty(&value(&name)?)?),
+            "--width" => width = Some(parse_number(&name, &value(&name)?)?),
+            "--height" => height = Some(parse_number(&name, &value(&name)?)?),
+            "--mines" => mines = Some(parse_number(&name, &value(&name)?)?),
+            "--seed" => seed = Some(parse_number(&name, &value(&name)?)?),
+            "--no-guess" => no_guess = true,
+            "--name" => player_name = Some(value(&name)?),
+            "--resume" => resume = true,
+            "--record" => record = Some(value(&name)?),
+            "--replay" => replay = Some(value(&name)?),
+            "--replay-speed" => match parse_number::<f64>(&name, &value(&name)?)? {
+                x if x > 0.0 && x.is_finite() => replay_speed = Some(x),
+                _ => return Err("--replay-speed must be a positive number".to_string()),
+            },
+            "--telnet" => telnet = Some(value(&name)?),
+            "--max-clients" => match parse_number(&name, &value(&name)?)? {
+                0 => return Err("--max-clients must be at least 1".to_string()),
+                n => max_clients = Some(n),
+            },
+            "--idle-timeout" => match parse_number(&name, &value(&name)?)? {
+                0 => return Err("--idle-timeout must be at least 1 second".to_string()),
+                n => idle_timeout = Some(Duration::from_secs(n)),
+            },
+            _ => return Err(format!("unknown option '{}'", arg)),
+        }
+    }
+
+    if telnet.is_none() && (max_clients.is_some() || idle_timeout.is_some()) {
+        return Err("--max-clients and --idle-timeout require --telnet".to_string());
+    }
+
+    if telnet.is_some() && seed.is_some() {
+        return Err("--seed cannot be used with --telnet".to_string());
+    }
+
+    if telnet.is_some() && (record.is_some() || replay.is_some() || resume) {
+        return Err("--record, --replay and --resume cannot be used with --telnet".to_string());
+    }
+
+    if replay_speed.is_some() && replay.is_none() {
+        return Err("--replay-speed requires --replay".to_string());
+    }
+
+    let has_game_options = difficulty.is_some()
+        || width.is_some()
+        || height.is_some()
+        || mines.is_some()
+        || seed.is_some()
+        || no_guess;
+    if replay.is_some() && (has_game_options || player_name.is_some() || record.is_some()) {
+        return Err("--replay cannot be used with options for a new game".to_string());
+    }
+
+    if resume && (has_game_options || record.is_some() || replay.is_some()) {
+        return Err("--resume cannot be used with options for a new game".to_string());
+    }
+
+    let custom = match (width, height, mines) {
+        (None, None, None) => None,
+        (Some(width), Some(height), Some(mines)) => {
+            let terminal_size = terminal_size.filter(|_| telnet.is_none());
+            validate_size(width, height, mines, terminal_size)?;
+            Some(Difficulty::Custom(width, height, mines))
+        }
+        _ => return Err("--width, --height and --mines must be specified together".to_string()),
+    };
+
+    let difficulty = match (difficulty, custom) {
+        (Some(_), Some(_)) => {
+            return Err("--difficulty cannot be used with --width/--height/--mines".to_string())
+        }
+        (difficulty, custom) => difficulty.or(custom).unwrap_or(Difficulty::Preset(0)),
+    };
+
+    Ok(Command::Play(Options {
+        difficulty,
+        seed,
+        no_guess,
+        player_name,
+        resume,
+        has_game_options,
+        record,
+        replay,
+        replay_speed: replay_speed.unwrap_or(1.0),
+        telnet,
+        max_clients: max_clients.unwrap_or(16),
+        idle_timeout: idle_timeout.unwrap_or(Duration::from_secs(300)),
+    }))
+}
@@ -1,20 +1,136 @@
+use crate::scores::{
+    record_score, Leaderboard, Score, MAX_NAME_LENGTH, NO_GUESS_SUFFIX, PRESET_NAMES,
+};
+use crate::settings::{FormAction, SettingsForm};
+use crate::solver::{Field, Tile};
 use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::fs::{self, File};
+use std::io::{self, Write};
 use std::num::TryFromIntError;
+use std::path::PathBuf;
+use tui::color::Color;
 use tui::game::Game;
 use tui::key::{Key, MouseButton};
-use tui::screen::ScreenBuffer;
-
-const BLACK: usize = 30;
-const RED: usize = 31;
-const GREEN: usize = 32;
-const YELLOW: usize = 33;
-const BLUE: usize = 34;
-const CYAN: usize = 36;
-const WHITE: usize = 37;
-const GRAY: usize = 90;
-const NUMBER_COLORS: [usize; 9] = [BLACK, CYAN, GREEN, RED, BLUE, RED, GREEN, CYAN, BLACK];
+use tui::replay::Replay;
+use tui::screen::{Rect, ScreenBuffer, Style};
+use tui::widget::{Align, InputAction, Label, Panel, TextInput, Widget};
+
+pub const TICKS_PER_SECOND: usize = 60;
+
+pub const BLACK: Color = Color::Black;
+const RED: Color = Color::Red;
+const GREEN: Color = Color::Green;
+pub const YELLOW: Color = Color::Yellow;
+const MAGENTA: Color = Color::Magenta;
+const WHITE: Color = Color::White;
+const GRAY: Color = Color::BrightBlack;
+// The classic palette, which terminals without 24-bit colors get the nearest colors of. 1 is
+// lighter than the classic blue so that it doesn't become the same color as 4 on 16 colors.
+const NUMBER_COLORS: [Color; 9] = [
+    Color::Rgb(0, 0, 0),
+    Color::Rgb(60, 60, 255),
+    Color::Rgb(0, 128, 0),
+    Color::Rgb(255, 0, 0),
+    Color::Rgb(0, 0, 128),
+    Color::Rgb(128, 0, 0),
+    Color::Rgb(0, 128, 128),
+    Color::Rgb(0, 0, 0),
+    Color::Rgb(128, 128, 128),
+];
 
 static DIFFICULTIES: [(usize, usize, usize); 3] = [(9, 9, 10), (16, 16, 40), (30, 16, 99)];
+const NO_GUESS_ATTEMPTS: usize = 1000;
+const SAVE_HEADER: &str = "tui-minesweeper-save 1";
+pub const SAVE_FILE: &str = "minesweeper-save.txt";
+// Rows taken by the status line and the help text
+const RESERVED_ROWS: usize = 8;
+// Limits for boards not checked against a terminal, e.g. from saves and replays
+const MAX_SIDE: usize = 99_999;
+const MAX_CELLS: usize = 1_000_000;
+
+#[derive(Clone, Copy)]
+pub enum Difficulty {
+    Preset(usize),
+    Custom(usize, usize, usize),
+}
+
+impl Difficulty {
+    fn size(&self) -> (usize, usize, usize) {
+        match *self {
+            Difficulty::Preset(i) => DIFFICULTIES[i],
+            Difficulty::Custom(width, height, mines) => (width, height, mines),
+        }
+    }
+
+    fn id(&self) -> String {
+        match *self {
+            Difficulty::Preset(i) => PRESET_NAMES[i].to_string(),
+            Difficulty::Custom(width, height, mines) => {
+                format!("custom-{}x{}-{}", width, height, mines)
+            }
+        }
+    }
+
+    fn from_id(id: &str) -> Option<Self> {
+        if let Some(i) = PRESET_NAMES.iter().position(|x| *x == id) {
+            return Some(Difficulty::Preset(i));
+        }
+
+        let (width, rest) = id.strip_prefix("custom-")?.split_once('x')?;
+        let (height, mines) = rest.split_once('-')?;
+        let (width, height, mines) = (
+            width.parse().ok()?,
+            height.parse().ok()?,
+            mines.parse().ok()?,
+        );
+        validate_size(width, height, mines, None).ok()?;
+        Some(Difficulty::Custom(width, height, mines))
+    }
+
+    fn next(&self) -> Self {
+        match *self {
+            Difficulty::Preset(i) => Difficulty::Preset((i + 1) % DIFFICULTIES.len()),
+            Difficulty::Custom(..) => Difficulty::Preset(0),
+        }
+    }
+}
+
+pub fn validate_size(
+    width: usize,
+    height: usize,
+    mines: usize,
+    terminal_size: Option<(usize, usize)>,
+) -> Result<(), String> {
+    if width < 2 || height < 2 {
+        return Err("Width and height must be at least 2".to_string());
+    }
+
+    let cells = width
+        .checked_mul(height)
+        .filter(|&cells| width <= MAX_SIDE && height <= MAX_SIDE && cells <= MAX_CELLS)
+        .ok_or(format!(
+            "The board is too large (at most {} per side and {} cells)",
+            MAX_SIDE, MAX_CELLS
+        ))?;
+
+    if mines == 0 || mines >= cells {
+        return Err(format!("Mines must be between 1 and {}", cells - 1));
+    }
+
+    if let Some((columns, rows)) = terminal_size {
+        if width * 3 > columns || height.saturating_add(RESERVED_ROWS) > rows {
+            return Err(format!(
+                "The board must fit the terminal (at most {}x{})",
+                columns / 3,
+                rows.saturating_sub(RESERVED_ROWS),
+            ));
+        }
+    }
+
+    Ok(())
+}
 
 struct Cell {
     pub is_mine: bool,
@@ -28,6 +144,10 @@ pub struct Board {
     height: usize,
     cells: Vec<Cell>,
     mines: usize,
+    seed: u64,
+    no_guess: bool,
+    // Set when no layout solvable without guessing was found for a no-guess board
+    needs_guess: bool,
     is_initialized: bool,
     safe_cells: usize,
     revealed_cells: usize,
@@ -38,7 +158,7 @@ fn cells_coord(width: usize, height: usize) -> impl Iterator<Item = (usize, usiz
     (0..height).flat_map(move |y| (0..width).map(move |x| (y, x)))
 }
 
-fn adjacent_cells_coord(
+pub fn adjacent_cells_coord(
     x: usize,
     y: usize,
     width: usize,
@@ -58,7 +178,7 @@ fn adjacent_cells_coord(
 }
 
 impl Board {
-    fn new(width: usize, height: usize, mines: usize) -> Self {
+    fn new(width: usize, height: usize, mines: usize, seed: u64, no_guess: bool) -> Self {
         let cells: Vec<_> = (0..width * height)
             .map(|_| Cell {
                 is_mine: false,
@@ -73,6 +193,9 @@ impl Board {
             height,
             cells,
             mines,
+            seed,
+            no_guess,
+            needs_guess: false,
             is_initialized: false,
             safe_cells: height * width - mines,
             revealed_cells: 0,
@@ -81,20 +204,99 @@ impl Board {
     }
 
     fn init(&mut self, excluded_x: usize, excluded_y: usize) {
-        let excluded_cell = excluded_y * self.width + excluded_x;
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
 
-        let mut mines: Vec<_> = (0..(self.width * self.height - 1))
+        if !self.no_guess {
+            self.place_mines(&mut rng, &[(excluded_x, excluded_y)]);
+            return;
+        }
+
+        let mut excluded: Vec<_> =
+            adjacent_cells_coord(excluded_x, excluded_y, self.width, self.height).collect();
+        excluded.push((excluded_x, excluded_y));
+        if self.width * self.height - excluded.len() < self.mines {
+            excluded = vec![(excluded_x, excluded_y)];
+        }
+
+        for _ in 0..NO_GUESS_ATTEMPTS {
+            self.place_mines(&mut rng, &excluded);
+            if self.is_solvable_from(excluded_x, excluded_y) {
+                return;
+            }
+        }
+
+        // Dense boards may have no such layout, so the last one is kept and the player told
+        self.needs_guess = true;
+    }
+
+    fn place_mines(&mut self, rng: &mut ChaCha8Rng, excluded: &[(usize, usize)]) {
+        let excluded_cells: Vec<_> = excluded.iter().map(|&(x, y)| y * self.width + x).collect();
+
+        let mut mines: Vec<_> = (0..(self.width * self.height - excluded_cells.len()))
             .map(|x| x < self.mines)
             .collect();
-        mines.shuffle(&mut rand::thread_rng());
+        mines.shuffle(rng);
+
+        self.cells.iter_mut().for_each(|cell| {
+            cell.is_mine = false;
+            cell.adjacent_mines = 0;
+        });
 
         self.cells
             .iter_mut()
             .enumerate()
-            .filter(|(i, _)| *i != excluded_cell)
-            .zip(mines.into_iter())
+            .filter(|(i, _)| !excluded_cells.contains(i))
+            .zip(mines)
             .for_each(|((_, cell), is_mine)| cell.is_mine = is_mine);
 
+        self.count_adjacent_mines();
+    }
+
+    // Each cell is stored as a digit holding the mine, revealed and flagged bits
+    fn encode_cells(&self) -> Vec<String> {
+        self.cells
+            .chunks(self.width)
+            .map(|row| {
+                row.iter()
+                    .map(|cell| {
+                        let bits = cell.is_mine as u8
+                            | (cell.is_revealed as u8) << 1
+                            | (cell.is_flagged as u8) << 2;
+                        (b'0' + bits) as char
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn decode_cells(&mut self, rows: &[&str]) -> Option<()> {
+        if rows.len() != self.height || rows.iter().any(|row| row.len() != self.width) {
+            return None;
+        }
+
+        for (cell, bits) in self
+            .cells
+            .iter_mut()
+            .zip(rows.iter().flat_map(|x| x.bytes()))
+        {
+            let bits = bits.checked_sub(b'0').filter(|&x| x < 8)?;
+            cell.is_mine = bits & 1 != 0;
+            cell.is_revealed = bits & 2 != 0;
+            cell.is_flagged = bits & 4 != 0;
+            cell.adjacent_mines = 0;
+        }
+
+        if self.cells.iter().filter(|x| x.is_mine).count() != self.mines {
+            return None;
+        }
+
+        self.count_adjacent_mines();
+        self.revealed_cells = self.cells.iter().filter(|x| x.is_revealed).count();
+        self.revealed_mine = self.cells.iter().any(|x| x.is_revealed && x.is_mine);
+        Some(())
+    }
+
+    fn count_adjacent_mines(&mut self) {
         for (x, y) in cells_coord(self.height, self.width) {
             for (adj_x, adj_y) in adjacent_cells_coord(x, y, self.width, self.height) {
                 if self.cells[adj_y * self.width + adj_x].is_mine {
@@ -104,6 +306,56 @@ impl Board {
         }
     }
 
+    fn reveal_on_field(&self, field: &mut Field, x: usize, y: usize) {
+        if field.tile_at(x, y) != Tile::Hidden {
+            return;
+        }
+
+        let adjacent_mines = self.cell_at(x, y).adjacent_mines;
+        field.set_tile(x, y, Tile::Revealed(adjacent_mines));
+
+        if adjacent_mines == 0 {
+            for (adj_x, adj_y) in adjacent_cells_coord(x, y, self.width, self.height) {
+                self.reveal_on_field(field, adj_x, adj_y);
+            }
+        }
+    }
+
+    fn is_solvable_from(&self, x: usize, y: usize) -> bool {
+        let mut field = Field::new(self.width, self.height, self.mines);
+        self.reveal_on_field(&mut field, x, y);
+
+        loop {
+            let deduction = field.deduce();
+            if deduction.is_empty() {
+                break;
+            }
+
+            for (x, y) in deduction.mines {
+                field.set_tile(x, y, Tile::Mine);
+            }
+
+            for (x, y) in deduction.safe {
+                self.reveal_on_field(&mut field, x, y);
+            }
+        }
+
+        !field.tiles.contains(&Tile::Hidden)
+    }
+
+    fn field(&self) -> Field {
+        let mut field = Field::new(self.width, self.height, self.mines);
+
+        for (x, y) in cells_coord(self.height, self.width) {
+            let cell = self.cell_at(x, y);
+            if cell.is_revealed && !cell.is_mine {
+                field.set_tile(x, y, Tile::Revealed(cell.adjacent_mines));
+            }
+        }
+
+        field
+    }
+
     fn cell_at(&self, x: usize, y: usize) -> &Cell {
         &self.cells[y * self.width + x]
     }
@@ -184,6 +436,13 @@ impl Board {
     }
 }
 
+struct Hint {
+    x: usize,
+    y: usize,
+    probability: f64,
+    unflagged_mines: usize,
+}
+
 enum GameResult {
     Success,
     Failure,
@@ -192,27 +451,299 @@ enum GameResult {
 pub struct MineSweeper {
     cursor_x: usize,
     cursor_y: usize,
-    difficulty: usize,
+    difficulty: Difficulty,
+    no_guess: bool,
     board: Board,
+    terminal_size: Option<(usize, usize)>,
+    settings: Option<SettingsForm>,
     hold_mouse_buttons: (bool, bool),
     result: Option<GameResult>,
     ticks_elapsed: usize,
     is_started: bool,
+    is_paused: bool,
+    idle_ticks: usize,
+    idle_pause_ticks: Option<usize>,
+    hint: Option<Hint>,
+    hints_used: usize,
+    player_name: String,
+    scores_path: Option<PathBuf>,
+    records_scores: bool,
+    save_path: Option<PathBuf>,
+    name_input: Option<TextInput>,
+    record_message: Option<String>,
+    leaderboard: Option<Leaderboard>,
 }
 
 impl MineSweeper {
-    pub fn new(difficulty: usize) -> Self {
-        let (width, height, mines) = DIFFICULTIES[difficulty];
+    pub fn new(difficulty: Difficulty, no_guess: bool, seed: u64) -> Self {
+        let (width, height, mines) = difficulty.size();
 
         MineSweeper {
             cursor_x: 0,
             cursor_y: 0,
             difficulty,
-            board: Board::new(width, height, mines),
+            no_guess,
+            board: Board::new(width, height, mines, seed, no_guess),
+            terminal_size: None,
+            settings: None,
             hold_mouse_buttons: (false, false),
             result: None,
             ticks_elapsed: 0,
             is_started: false,
+            is_paused: false,
+            idle_ticks: 0,
+            idle_pause_ticks: None,
+            hint: None,
+            hints_used: 0,
+            player_name: String::new(),
+            scores_path: None,
+            records_scores: false,
+            save_path: None,
+            name_input: None,
+            record_message: None,
+            leaderboard: None,
+        }
+    }
+
+    pub fn set_terminal_size(&mut self, terminal_size: Option<(usize, usize)>) {
+        self.terminal_size = terminal_size;
+    }
+
+    pub fn set_scores(&mut self, player_name: String, scores_path: Option<PathBuf>) {
+        self.player_name = player_name;
+        self.records_scores = scores_path.is_some();
+        self.scores_path = scores_path;
+    }
+
+    pub fn replay_metadata(&self) -> Vec<(String, String)> {
+        let terminal_size = match self.terminal_size {
+            Some((columns, rows)) => format!("{}x{}", columns, rows),
+            None => "-".to_string(),
+        };
+        let idle_pause = match self.idle_pause_ticks {
+            Some(ticks) => (ticks / TICKS_PER_SECOND).to_string(),
+            None => "-".to_string(),
+        };
+
+        vec![
+            ("seed".to_string(), self.board.seed.to_string()),
+            ("difficulty".to_string(), self.difficulty.id()),
+            ("no-guess".to_string(), (self.no_guess as u8).to_string()),
+            (
+                "scores".to_string(),
+                (self.records_scores as u8).to_string(),
+            ),
+            ("terminal".to_string(), terminal_size),
+            ("player".to_string(), self.player_name.clone()),
+            ("idle-pause".to_string(), idle_pause),
+        ]
+    }
+
+    // Scores are never saved while replaying, but the name prompt is shown as it was recorded
+    pub fn from_replay(replay: &Replay) -> Result<Self, String> {
+        let field = |key: &str| {
+            replay
+                .metadata(key)
+                .ok_or_else(|| format!("The replay has no {}", key))
+        };
+
+        let seed = field("seed")?
+            .parse()
+            .map_err(|_| "Invalid seed in the replay")?;
+        let difficulty =
+            Difficulty::from_id(field("difficulty")?).ok_or("Invalid difficulty in the replay")?;
+        let terminal_size = field("terminal")?
+            .split_once('x')
+            .and_then(|(columns, rows)| Some((columns.parse().ok()?, rows.parse().ok()?)));
+
+        let mut game = MineSweeper::new(difficulty, field("no-guess")? == "1", seed);
+        game.terminal_size = terminal_size;
+        game.records_scores = field("scores")? == "1";
+        game.player_name = field("player")?.to_string();
+        game.set_idle_pause(replay.metadata("idle-pause").and_then(|x| x.parse().ok()));
+        Ok(game)
+    }
+
+    pub fn set_idle_pause(&mut self, seconds: Option<usize>) {
+        self.idle_pause_ticks = seconds.map(|x| x * TICKS_PER_SECOND);
+    }
+
+    fn is_running(&self) -> bool {
+        self.is_started && self.result.is_none()
+    }
+
+    fn pause(&mut self) {
+        if self.is_running() {
+            self.is_paused = true;
+        }
+    }
+
+    pub fn set_save_path(&mut self, save_path: Option<PathBuf>) {
+        self.save_path = save_path;
+    }
+
+    pub fn save(&self, dest: &mut impl Write) -> io::Result<()> {
+        writeln!(dest, "{}", SAVE_HEADER)?;
+        writeln!(dest, "difficulty {}", self.difficulty.id())?;
+        // 2 if no layout without guessing was found
+        let no_guess = self.no_guess as u8 + self.board.needs_guess as u8;
+        writeln!(dest, "no-guess {}", no_guess)?;
+        writeln!(dest, "seed {}", self.board.seed)?;
+        writeln!(dest, "cursor {} {}", self.cursor_x, self.cursor_y)?;
+        writeln!(dest, "ticks {}", self.ticks_elapsed)?;
+        writeln!(dest, "hints {}", self.hints_used)?;
+        writeln!(dest, "initialized {}", self.board.is_initialized as u8)?;
+        for row in self.board.encode_cells() {
+            writeln!(dest, "{}", row)?;
+        }
+        Ok(())
+    }
+
+    pub fn load(source: &str) -> Option<Self> {
+        let mut lines = source.lines();
+        if lines.next()? != SAVE_HEADER {
+            return None;
+        }
+
+        let mut field = |key: &str| {
+            let line = lines.next()?;
+            line.strip_prefix(key)?.strip_prefix(' ')
+        };
+
+        let difficulty = Difficulty::from_id(field("difficulty")?)?;
+        let no_guess = field("no-guess")?;
+        let needs_guess = no_guess == "2";
+        let no_guess = no_guess != "0";
+        let seed = field("seed")?.parse().ok()?;
+        let (cursor_x, cursor_y) = field("cursor")?.split_once(' ')?;
+        let (cursor_x, cursor_y) = (cursor_x.parse().ok()?, cursor_y.parse().ok()?);
+        let ticks_elapsed = field("ticks")?.parse().ok()?;
+        let hints_used = field("hints")?.parse().ok()?;
+        let is_initialized = field("initialized")? == "1";
+
+        let mut game = MineSweeper::new(difficulty, no_guess, seed);
+        if !game.board.contains_coord(cursor_x, cursor_y) {
+            return None;
+        }
+
+        let rows: Vec<_> = lines.collect();
+        game.board.decode_cells(&rows)?;
+        game.board.is_initialized = is_initialized;
+        game.board.needs_guess = needs_guess;
+        game.cursor_x = cursor_x;
+        game.cursor_y = cursor_y;
+        game.ticks_elapsed = ticks_elapsed;
+        game.hints_used = hints_used;
+        game.is_started = is_initialized || game.board.cells.iter().any(|x| x.is_flagged);
+        Some(game)
+    }
+
+    fn save_on_quit(&self) -> io::Result<()> {
+        let path = match &self.save_path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        // Mines aren't placed until the first reveal, and flags alone aren't worth keeping
+        if !self.board.is_initialized || self.result.is_some() {
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = File::create(path)?;
+        self.save(&mut file)
+    }
+
+    fn restart(&mut self, difficulty: Difficulty, no_guess: bool) {
+        // Derive the next seed from the current one so that a whole session can be replayed
+        let seed = ChaCha8Rng::seed_from_u64(self.board.seed).gen();
+        let previous = std::mem::replace(self, MineSweeper::new(difficulty, no_guess, seed));
+        self.terminal_size = previous.terminal_size;
+        self.idle_pause_ticks = previous.idle_pause_ticks;
+        self.player_name = previous.player_name;
+        self.scores_path = previous.scores_path;
+        self.records_scores = previous.records_scores;
+        self.save_path = previous.save_path;
+    }
+
+    fn finish_with_success(&mut self) {
+        self.result = Some(GameResult::Success);
+
+        if self.hints_used > 0 {
+            self.record_message = Some("Not recorded in the best times (hints used)".to_string());
+        } else if self.records_scores {
+            self.name_input = Some(TextInput::new(&self.player_name).max_length(MAX_NAME_LENGTH));
+        }
+    }
+
+    // Boards which turned out to need guessing are ranked with normal ones
+    fn score_category(&self) -> String {
+        if self.no_guess && !self.board.needs_guess {
+            format!("{}{}", self.difficulty.id(), NO_GUESS_SUFFIX)
+        } else {
+            self.difficulty.id()
+        }
+    }
+
+    fn save_score(&mut self, name: String) {
+        let score = Score::new(self.score_category(), self.ticks_elapsed, &name);
+        self.player_name = name;
+
+        let path = match &self.scores_path {
+            Some(path) => path,
+            None => {
+                self.record_message = Some(format!("{:.2}s (Not saved)", score.seconds()));
+                return;
+            }
+        };
+
+        self.record_message = Some(match record_score(path, &score) {
+            Ok(true) => format!("New record! {:.2}s", score.seconds()),
+            Ok(false) => format!("Recorded {:.2}s", score.seconds()),
+            Err(err) => format!("Failed to save the score: {}", err),
+        });
+    }
+
+    fn process_name_key(&mut self, key: Key) {
+        let input = match &mut self.name_input {
+            Some(input) => input,
+            None => return,
+        };
+
+        match input.process_key(&key) {
+            InputAction::None => (),
+            InputAction::Submit => {
+                let name = input.value().to_string();
+                self.name_input = None;
+                self.save_score(name);
+            }
+            InputAction::Cancel => self.name_input = None,
+        }
+    }
+
+    fn open_settings(&mut self) {
+        let (width, height, mines) = self.difficulty.size();
+        self.settings = Some(SettingsForm::new(width, height, mines));
+    }
+
+    fn process_settings_key(&mut self, key: Key) {
+        let settings = match &mut self.settings {
+            Some(settings) => settings,
+            None => return,
+        };
+
+        match settings.process_key(key) {
+            FormAction::None => (),
+            FormAction::Cancel => self.settings = None,
+            FormAction::Submit(width, height, mines) => {
+                match validate_size(width, height, mines, self.terminal_size) {
+                    Ok(()) => self.restart(Difficulty::Custom(width, height, mines), self.no_guess),
+                    Err(error) => settings.set_error(error),
+                }
+            }
         }
     }
 
@@ -280,6 +811,7 @@ impl MineSweeper {
         }
 
         self.is_started = true;
+        self.hint = None;
 
         if chord {
             self.board.chord_reveal(self.cursor_x, self.cursor_y);
@@ -290,56 +822,181 @@ impl MineSweeper {
         if self.board.revealed_mine {
             self.result = Some(GameResult::Failure);
         } else if self.board.is_cleared() {
-            self.result = Some(GameResult::Success);
+            self.finish_with_success();
         }
     }
 
     pub fn flag(&mut self) {
         self.is_started = true;
+        self.hint = None;
 
         if self.result.is_none() {
             self.board.flag(self.cursor_x, self.cursor_y);
         }
     }
+
+    pub fn hint(&mut self) {
+        if self.result.is_some() {
+            return;
+        }
+
+        self.hints_used += 1;
+
+        if !self.board.is_initialized {
+            self.hint = Some(Hint {
+                x: self.cursor_x,
+                y: self.cursor_y,
+                probability: 0.0,
+                unflagged_mines: 0,
+            });
+            return;
+        }
+
+        let analysis = self.board.field().analyze();
+        let distance =
+            |&(x, y): &(usize, usize)| x.abs_diff(self.cursor_x) + y.abs_diff(self.cursor_y);
+        let probability_at = |(x, y): (usize, usize)| {
+            analysis.probabilities[y * self.board.width + x].unwrap_or(1.0)
+        };
+        let unflagged_mines = analysis
+            .deduction
+            .mines
+            .iter()
+            .filter(|&&(x, y)| !self.board.cell_at(x, y).is_flagged)
+            .count();
+
+        let safe = analysis
+            .deduction
+            .safe
+            .iter()
+            .copied()
+            .filter(|&(x, y)| !self.board.cell_at(x, y).is_flagged)
+            .min_by_key(distance);
+
+        let lowest_risk = || {
+            cells_coord(self.board.height, self.board.width)
+                .filter(|&(x, y)| {
+                    let cell = self.board.cell_at(x, y);
+                    !cell.is_revealed && !cell.is_flagged
+                })
+                .min_by(|a, b| {
+                    probability_at(*a)
+                        .total_cmp(&probability_at(*b))
+                        .then(distance(a).cmp(&distance(b)))
+                })
+        };
+
+        self.hint = safe.or_else(lowest_risk).map(|(x, y)| Hint {
+            x,
+            y,
+            probability: probability_at((x, y)),
+            unflagged_mines,
+        });
+    }
 }
 
 impl Game for MineSweeper {
     fn render(&self) -> ScreenBuffer {
         let mut screen = ScreenBuffer::new();
 
-        let time = self.ticks_elapsed / 60;
+        if let Some(settings) = &self.settings {
+            settings.render(&mut screen);
+            return screen;
+        }
+
+        if let Some(leaderboard) = &self.leaderboard {
+            leaderboard.render(&mut screen);
+            return screen;
+        }
+
+        if let Some((columns, rows)) = self.terminal_size {
+            // The status line, the board and the line the cursor is parked on
+            let (min_columns, min_rows) = (self.board.width * 3, self.board.height + 2);
+            if columns < min_columns || rows < min_rows {
+                screen.write("Terminal too small");
+                screen.new_line();
+                screen.write(&format!(
+                    "Need {}x{}, got {}x{}",
+                    min_columns, min_rows, columns, rows
+                ));
+                return screen;
+            }
+        }
+
+        let time = self.ticks_elapsed / TICKS_PER_SECOND;
         let flags = self.board.cells.iter().filter(|x| x.is_flagged).count();
         let mines = self.board.mines.saturating_sub(flags);
         screen.write_color(&format!(" {:0>3}   {:0>3} ", mines, time), RED, WHITE);
+        screen.write(&format!(" Seed: {}", self.board.seed));
+        if self.board.needs_guess {
+            screen.write(" (No-guess failed, guessing may be needed)");
+        } else if self.no_guess {
+            screen.write(" (No-guess)");
+        }
         screen.new_line();
 
+        if self.is_paused {
+            // The board is hidden so that it can't be studied while the timer is stopped
+            let board = Rect::new(0, 1, self.board.width * 3, self.board.height);
+            screen.fill_rect(board, ' ', Style::new().bg(GRAY));
+
+            // The box is left out on boards too small for it
+            let dialog = board.centered(12, 3);
+            let style = Style::new().fg(BLACK).bg(WHITE);
+            if (dialog.width, dialog.height) == (12, 3) {
+                Panel::new().style(style).render(&mut screen, dialog);
+            }
+
+            let text = Rect::new(board.x, board.centered(6, 1).y, board.width, 1);
+            Label::new("PAUSED")
+                .align(Align::Center)
+                .style(style.bold())
+                .render(&mut screen, text);
+
+            screen.new_line();
+            screen.write("Paused - Press any key to resume");
+            return screen;
+        }
+
         for y in 0..self.board.height {
             for x in 0..self.board.width {
                 let cell = self.board.cell_at(x, y);
 
-                let bg = if self.cursor_x == x && self.cursor_y == y {
-                    YELLOW
+                let hint = self.hint.as_ref().filter(|hint| hint.x == x && hint.y == y);
+
+                let bg = if let Some(hint) = hint {
+                    if hint.probability <= 0.0 {
+                        GREEN
+                    } else {
+                        MAGENTA
+                    }
                 } else if cell.is_revealed {
                     WHITE
                 } else {
                     GRAY
                 };
 
+                let mut style = Style::new().bg(bg);
+                if self.cursor_x == x && self.cursor_y == y {
+                    style = style.reverse();
+                }
+
                 if cell.is_revealed {
                     if cell.is_mine {
-                        screen.write_color(" X ", RED, bg);
+                        screen.write_styled(" X ", style.fg(RED));
                     } else if cell.adjacent_mines > 0 {
                         let fg = NUMBER_COLORS[cell.adjacent_mines];
-                        screen.write_color(&format!(" {} ", cell.adjacent_mines), fg, bg);
+                        let text = format!(" {} ", cell.adjacent_mines);
+                        screen.write_styled(&text, style.fg(fg).bold());
                     } else {
-                        screen.write_color("   ", WHITE, bg);
+                        screen.write_styled("   ", style.fg(BLACK));
                     }
                 } else if cell.is_flagged {
-                    screen.write_color("[", WHITE, bg);
-                    screen.write_color("F", RED, bg);
-                    screen.write_color("]", WHITE, bg);
+                    screen.write_styled("[", style.fg(WHITE));
+                    screen.write_styled("F", style.fg(RED));
+                    screen.write_styled("]", style.fg(WHITE));
                 } else {
-                    screen.write_color("[ ]", WHITE, bg);
+                    screen.write_styled("[ ]", style.fg(WHITE));
                 }
             }
 
@@ -347,15 +1004,58 @@ impl Game for MineSweeper {
         }
         match self.result {
             Some(GameResult::Success) => {
-                screen.write("All safe cells revealed! You win! Press R to retry")
+                screen.write(&format!(
+                    "All safe cells revealed! You win! (Hints used: {}) Press R to retry",
+                    self.hints_used,
+                ));
+                screen.new_line();
+
+                if let Some(input) = &self.name_input {
+                    screen.write("Enter your name: ");
+                    let (x, y) = screen.cursor();
+                    input.render(&mut screen, Rect::new(x, y, MAX_NAME_LENGTH + 1, 1));
+                    screen.new_line();
+                    screen.write("Enter - Save the time, Esc - Skip");
+                } else if let Some(message) = &self.record_message {
+                    screen.write_color(&format!(" {} ", message), BLACK, YELLOW);
+                    screen.new_line();
+                    screen.write("B - Best times");
+                }
             }
-            Some(GameResult::Failure) => screen.write("You lose... Press R to retry"),
+            Some(GameResult::Failure) => screen.write(&format!(
+                "You lose... (Hints used: {}) Press R to retry",
+                self.hints_used,
+            )),
             None => {
-                screen.write("Arrow (or HJKL) - Move cursor, Shift + HJKL - Smart cursor");
+                if let Some(hint) = &self.hint {
+                    if hint.probability <= 0.0 {
+                        screen.write("Hint: The green cell is safe");
+                    } else {
+                        screen.write(&format!(
+                            "Hint: No safe cell found, the magenta cell has {:.0}% risk",
+                            hint.probability * 100.0,
+                        ));
+                    }
+                    if hint.unflagged_mines > 0 {
+                        screen.write(&format!(
+                            " ({} certain mines unflagged)",
+                            hint.unflagged_mines
+                        ));
+                    }
+                    screen.new_line();
+                }
+
+                screen.write(
+                    "Arrow (or HJKL) - Move cursor, Shift + HJKL or Ctrl + Arrow - Smart cursor",
+                );
                 screen.new_line();
                 screen.write("A - Reveal, Space - Reveal (Can perform \"Chord\"), F - Flag");
                 screen.new_line();
-                screen.write("R - Retry, C - Change difficulty, Ctrl-C - Quit");
+                screen.write("R - Retry, C - Change difficulty, S - Custom board");
+                screen.new_line();
+                screen.write("G - Toggle no-guess mode, B - Best times, ? - Hint, P - Pause");
+                screen.new_line();
+                screen.write("Home/End/PgUp/PgDn - Jump to edge, Ctrl-C - Quit");
             }
         }
 
@@ -363,6 +1063,37 @@ impl Game for MineSweeper {
     }
 
     fn process_key(&mut self, key: Key) {
+        match key {
+            Key::FocusIn => return,
+            Key::FocusOut => {
+                self.pause();
+                return;
+            }
+            _ => self.idle_ticks = 0,
+        }
+
+        if self.is_paused {
+            self.is_paused = false;
+            return;
+        }
+
+        if self.settings.is_some() {
+            self.process_settings_key(key);
+            return;
+        }
+
+        if let Some(leaderboard) = &mut self.leaderboard {
+            if leaderboard.process_key(key) {
+                self.leaderboard = None;
+            }
+            return;
+        }
+
+        if self.name_input.is_some() {
+            self.process_name_key(key);
+            return;
+        }
+
         match key {
             Key::Character('k') | Key::ArrowUp => self.move_cursor(0, -1),
             Key::Character('j') | Key::ArrowDown => self.move_cursor(0, 1),
@@ -372,17 +1103,38 @@ impl Game for MineSweeper {
             Key::Character('J') => self.move_cursor_smart(0, 1),
             Key::Character('H') => self.move_cursor_smart(-1, 0),
             Key::Character('L') => self.move_cursor_smart(1, 0),
+            Key::Modified(modifiers, key) if modifiers.control || modifiers.shift => match *key {
+                Key::ArrowUp => self.move_cursor_smart(0, -1),
+                Key::ArrowDown => self.move_cursor_smart(0, 1),
+                Key::ArrowLeft => self.move_cursor_smart(-1, 0),
+                Key::ArrowRight => self.move_cursor_smart(1, 0),
+                _ => (),
+            },
+            Key::Home => self.move_cursor(-(self.cursor_x as isize), 0),
+            Key::End => self.move_cursor((self.board.width - 1 - self.cursor_x) as isize, 0),
+            Key::PageUp => self.move_cursor(0, -(self.cursor_y as isize)),
+            Key::PageDown => self.move_cursor(0, (self.board.height - 1 - self.cursor_y) as isize),
             Key::Character('f') | Key::Character('F') => self.flag(),
             Key::Character(' ') => self.reveal(true),
             Key::Character('a') | Key::Character('A') => self.reveal(false),
+            Key::Character('?') => self.hint(),
+            Key::Character('p') | Key::Character('P') => self.pause(),
             Key::Character('c') | Key::Character('C') => {
-                let difficulty = (self.difficulty + 1) % DIFFICULTIES.len();
-                *self = MineSweeper::new(difficulty);
+                self.restart(self.difficulty.next(), self.no_guess);
+            }
+            Key::Character('g') | Key::Character('G') => {
+                self.restart(self.difficulty, !self.no_guess);
             }
             Key::Character('r') | Key::Character('R') => {
-                *self = MineSweeper::new(self.difficulty);
+                self.restart(self.difficulty, self.no_guess);
+            }
+            Key::Character('s') | Key::Character('S') => self.open_settings(),
+            Key::Character('b') | Key::Character('B') => {
+                let leaderboard =
+                    Leaderboard::new(self.scores_path.as_ref(), self.score_category());
+                self.leaderboard = Some(leaderboard);
             }
-            Key::Mousedown(MouseButton::Left, x, y) if y >= 2 => {
+            Key::Mousedown(MouseButton::Left, x, y) if x >= 1 && y >= 2 => {
                 let x = (x - 1) / 3;
                 let y = y - 2;
                 if self.board.contains_coord(x, y) {
@@ -390,7 +1142,7 @@ impl Game for MineSweeper {
                 }
                 self.hold_mouse_buttons.0 = true;
             }
-            Key::Mouseup(MouseButton::Left, x, y) if y >= 2 => {
+            Key::Mouseup(MouseButton::Left, x, y) if x >= 1 && y >= 2 => {
                 let x = (x - 1) / 3;
                 let y = y - 2;
                 if self.board.contains_coord(x, y)
@@ -401,7 +1153,7 @@ impl Game for MineSweeper {
                 }
                 self.hold_mouse_buttons.0 = false;
             }
-            Key::Mousedown(MouseButton::Right, x, y) if y >= 2 => {
+            Key::Mousedown(MouseButton::Right, x, y) if x >= 1 && y >= 2 => {
                 let x = (x - 1) / 3;
                 let y = y - 2;
                 if self.board.contains_coord(x, y) {
@@ -410,7 +1162,7 @@ impl Game for MineSweeper {
                 }
                 self.hold_mouse_buttons.1 = true;
             }
-            Key::Mouseup(MouseButton::Right, x, y) if y >= 2 => {
+            Key::Mouseup(MouseButton::Right, x, y) if x >= 1 && y >= 2 => {
                 let x = (x - 1) / 3;
                 let y = y - 2;
                 if self.hold_mouse_buttons.0
@@ -423,13 +1175,93 @@ impl Game for MineSweeper {
                 }
                 self.hold_mouse_buttons.1 = false;
             }
+            Key::Mouseup(MouseButton::Middle, x, y) if x >= 1 && y >= 2 => {
+                let x = (x - 1) / 3;
+                let y = y - 2;
+                if self.board.contains_coord(x, y) && self.board.cell_at(x, y).is_revealed {
+                    self.set_cursor(x, y);
+                    self.reveal(true);
+                }
+            }
+            Key::Mousedrag(MouseButton::Left | MouseButton::Right, x, y) if x >= 1 && y >= 2 => {
+                let x = (x - 1) / 3;
+                let y = y - 2;
+                if self.board.contains_coord(x, y) {
+                    self.set_cursor(x, y);
+                }
+            }
             _ => (),
         }
     }
 
-    fn tick(&mut self) {
-        if self.is_started && self.result.is_none() {
-            self.ticks_elapsed += 1;
+    fn resize(&mut self, width: usize, height: usize) {
+        self.terminal_size = Some((width, height));
+    }
+
+    fn quit(&mut self) {
+        let _ = self.save_on_quit();
+    }
+
+    // Only the timer changes, once a second
+    fn tick(&mut self) -> bool {
+        if !self.is_running() || self.is_paused {
+            return false;
         }
+
+        self.ticks_elapsed += 1;
+        self.idle_ticks += 1;
+
+        if self
+            .idle_pause_ticks
+            .is_some_and(|limit| self.idle_ticks >= limit)
+        {
+            self.pause();
+            return true;
+        }
+
+        self.ticks_elapsed.is_multiple_of(TICKS_PER_SECOND)
+    }
+
+    // Nothing changes with time before the first reveal, after the end or while paused
+    fn tick_rate(&self) -> Option<usize> {
+        if !self.is_running() || self.is_paused {
+            return None;
+        }
+        Some(TICKS_PER_SECOND)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate(seed: u64, x: usize, y: usize, no_guess: bool) -> Board {
+        let mut board = Board::new(16, 16, 40, seed, no_guess);
+        board.init(x, y);
+        board
+    }
+
+    #[test]
+    fn same_seed_and_click_give_same_layout() {
+        for no_guess in [false, true] {
+            for seed in 0..20 {
+                let (x, y) = (seed as usize % 16, seed as usize * 7 % 16);
+                let board = generate(seed, x, y, no_guess);
+                assert_eq!(
+                    board.encode_cells(),
+                    generate(seed, x, y, no_guess).encode_cells()
+                );
+                assert!(!board.cell_at(x, y).is_mine);
+                assert_eq!(board.cells.iter().filter(|x| x.is_mine).count(), 40);
+            }
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_layouts() {
+        assert_ne!(
+            generate(1, 0, 0, false).encode_cells(),
+            generate(2, 0, 0, false).encode_cells()
+        );
     }
 }
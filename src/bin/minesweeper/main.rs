@@ -1,38 +1,160 @@
+mod cli;
 mod game;
+mod scores;
+mod settings;
+mod solver;
 
-use game::MineSweeper;
+use cli::{parse_args, Command, Options, USAGE};
+use game::{MineSweeper, SAVE_FILE};
 use nix::{
     sys::wait::wait,
     unistd::{fork, ForkResult},
 };
-use std::io::{stdin, stdout};
+use scores::{data_path, SCORES_FILE};
+use std::fs::File;
+use std::io::{stdin, stdout, BufWriter, Error, ErrorKind, Write};
 use std::net::TcpListener;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tui::rawmode::terminal_size;
+use tui::replay::{Recorder, Replay, ReplayPlayer};
 use tui::runner::{run_game_on_telnet, run_game_on_tty};
 
-fn listen_on_tcp(ipaddr: &str) -> std::io::Result<()> {
-    std::thread::spawn(|| loop {
-        let _ = wait();
+// Telnet players are paused when they stop typing for half the idle timeout, up to this, so
+// that it happens well before they are disconnected
+const MAX_IDLE_PAUSE_SECONDS: u64 = 30;
+
+fn listen_on_tcp(ipaddr: &str, options: &Options) -> std::io::Result<()> {
+    let clients = Arc::new(AtomicUsize::new(0));
+    let waited_clients = clients.clone();
+
+    std::thread::spawn(move || loop {
+        match wait() {
+            Ok(_) => {
+                waited_clients.fetch_sub(1, Ordering::SeqCst);
+            }
+            Err(_) => std::thread::sleep(Duration::from_millis(100)),
+        }
     });
 
     let listener = TcpListener::bind(ipaddr)?;
-    for stream in listener.incoming().flatten() {
-        if let Ok(ForkResult::Child) = unsafe { fork() } {
-            let read_stream = stream.try_clone()?;
-            let write_stream = stream;
-            read_stream.set_read_timeout(Some(std::time::Duration::from_secs(300)))?;
+    for mut stream in listener.incoming().flatten() {
+        if clients.load(Ordering::SeqCst) >= options.max_clients {
+            let _ = stream.write_all(b"Too many players are connected. Try again later.\r\n");
+            continue;
+        }
 
-            let _ = run_game_on_telnet(MineSweeper::new(0), read_stream, write_stream);
-            return Ok(());
+        clients.fetch_add(1, Ordering::SeqCst);
+
+        match unsafe { fork() } {
+            Ok(ForkResult::Child) => {
+                let read_stream = stream.try_clone()?;
+                let write_stream = stream;
+                read_stream.set_read_timeout(Some(options.idle_timeout))?;
+
+                let mut game =
+                    MineSweeper::new(options.difficulty, options.no_guess, rand::random());
+                let player_name = options.player_name.clone().unwrap_or_default();
+                game.set_scores(player_name, data_path(SCORES_FILE));
+                let idle_pause =
+                    (options.idle_timeout.as_secs() / 2).clamp(1, MAX_IDLE_PAUSE_SECONDS);
+                game.set_idle_pause(Some(idle_pause as usize));
+                let _ = run_game_on_telnet(game, read_stream, write_stream);
+                return Ok(());
+            }
+            Ok(ForkResult::Parent { .. }) => (),
+            Err(_) => {
+                clients.fetch_sub(1, Ordering::SeqCst);
+            }
         }
     }
     Ok(())
 }
 
+fn load_saved_game(path: &Path) -> std::io::Result<MineSweeper> {
+    // Removed even if it can't be loaded, so that a broken save isn't tried on every launch
+    let content = std::fs::read_to_string(path)?;
+    std::fs::remove_file(path)?;
+    MineSweeper::load(&content)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "The saved game is corrupted"))
+}
+
+// Errors before the game starts are reported like those of the arguments
+fn exit_with_error(message: impl std::fmt::Display) -> ! {
+    eprintln!("minesweeper: {}", message);
+    std::process::exit(2);
+}
+
 fn main() -> std::io::Result<()> {
-    let args: Vec<_> = std::env::args().take(3).collect();
-    let args_str: Vec<_> = args.iter().map(|x| x.as_str()).collect();
-    match args_str.as_slice() {
-        [_, "--telnet", ipaddr] => listen_on_tcp(ipaddr),
-        _ => run_game_on_tty(MineSweeper::new(0), stdin(), stdout()),
+    let terminal_size = terminal_size();
+
+    let options = match parse_args(std::env::args().skip(1), terminal_size) {
+        Ok(Command::Help) => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Ok(Command::Play(options)) => options,
+        Err(error) => {
+            eprintln!("minesweeper: {}", error);
+            eprintln!("Try 'minesweeper --help' for more information.");
+            std::process::exit(2);
+        }
+    };
+
+    if let Some(path) = &options.replay {
+        let file = File::open(path).unwrap_or_else(|err| {
+            exit_with_error(format!("cannot open replay '{}': {}", path, err))
+        });
+        let replay = Replay::load(file).unwrap_or_else(|err| exit_with_error(err));
+        let game = MineSweeper::from_replay(&replay).unwrap_or_else(|err| exit_with_error(err));
+        let mut player = ReplayPlayer::new(game, replay);
+        player.set_speed(options.replay_speed);
+        return run_game_on_tty(player, stdin(), stdout());
+    }
+
+    match &options.telnet {
+        Some(ipaddr) => listen_on_tcp(ipaddr, &options),
+        None => {
+            let save_path = data_path(SAVE_FILE);
+            let saved_game = match &save_path {
+                Some(path) if options.resume => match load_saved_game(path) {
+                    Ok(game) => Some(game),
+                    Err(err) if err.kind() == ErrorKind::NotFound => {
+                        exit_with_error("no saved game to resume")
+                    }
+                    Err(err) => exit_with_error(err),
+                },
+                Some(path) if !options.has_game_options && options.record.is_none() => {
+                    load_saved_game(path).ok()
+                }
+                None if options.resume => exit_with_error("no saved game to resume"),
+                _ => None,
+            };
+
+            let mut game = saved_game.unwrap_or_else(|| {
+                let seed = options.seed.unwrap_or_else(rand::random);
+                MineSweeper::new(options.difficulty, options.no_guess, seed)
+            });
+            game.set_terminal_size(terminal_size);
+            game.set_save_path(save_path);
+            let player_name = options
+                .player_name
+                .clone()
+                .or_else(|| std::env::var("USER").ok())
+                .unwrap_or_default();
+            game.set_scores(player_name, data_path(SCORES_FILE));
+
+            match &options.record {
+                Some(path) => {
+                    let dest = BufWriter::new(File::create(path)?);
+                    let metadata = game.replay_metadata();
+                    let recorder = Recorder::new(game, dest, &metadata)?;
+                    run_game_on_tty(recorder, stdin(), stdout())
+                }
+                None => run_game_on_tty(game, stdin(), stdout()),
+            }
+        }
     }
 }
@@ -0,0 +1,196 @@
+use crate::game::TICKS_PER_SECOND;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tui::key::Key;
+use tui::screen::{Rect, ScreenBuffer};
+use tui::widget::{Align, Table, Widget};
+
+pub const SCORES_FILE: &str = "minesweeper-scores.tsv";
+pub const MAX_NAME_LENGTH: usize = 16;
+pub const PRESET_NAMES: [&str; 3] = ["beginner", "intermediate", "expert"];
+const LEADERBOARD_SIZE: usize = 10;
+// Appended to the difficulty of scores on no-guess boards, which are kept apart as they're easier
+pub const NO_GUESS_SUFFIX: &str = "-no-guess";
+
+pub struct Score {
+    pub difficulty: String,
+    pub ticks: usize,
+    pub timestamp: u64,
+    pub name: String,
+}
+
+impl Score {
+    pub fn new(difficulty: String, ticks: usize, name: &str) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |x| x.as_secs());
+
+        let name = name
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(MAX_NAME_LENGTH)
+            .collect();
+
+        Score {
+            difficulty,
+            ticks,
+            timestamp,
+            name,
+        }
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(4, '\t');
+
+        Some(Score {
+            difficulty: fields.next()?.to_string(),
+            ticks: fields.next()?.parse().ok()?,
+            timestamp: fields.next()?.parse().ok()?,
+            name: fields.next()?.to_string(),
+        })
+    }
+
+    pub fn seconds(&self) -> f64 {
+        self.ticks as f64 / TICKS_PER_SECOND as f64
+    }
+
+    pub fn date(&self) -> String {
+        // Converts days since the epoch into a civil date (proleptic Gregorian calendar)
+        let z = (self.timestamp / 86400) as i64 + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as i64;
+
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+pub fn data_path(file_name: &str) -> Option<PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .filter(|x| !x.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|x| PathBuf::from(x).join(".local/share")))?;
+
+    Some(data_home.join("tui-games").join(file_name))
+}
+
+pub fn load_scores(path: &PathBuf) -> Vec<Score> {
+    let content = fs::read_to_string(path).unwrap_or_default();
+    content.lines().flat_map(Score::parse).collect()
+}
+
+pub fn best_scores<'a>(scores: &'a [Score], difficulty: &str, count: usize) -> Vec<&'a Score> {
+    let mut best: Vec<_> = scores
+        .iter()
+        .filter(|x| x.difficulty == difficulty)
+        .collect();
+    best.sort_by_key(|x| (x.ticks, x.timestamp));
+    best.truncate(count);
+    best
+}
+
+// Returns whether the score beats every previous score of the same difficulty
+pub fn record_score(path: &PathBuf, score: &Score) -> io::Result<bool> {
+    let is_new_record = best_scores(&load_scores(path), &score.difficulty, 1)
+        .first()
+        .is_none_or(|best| score.ticks < best.ticks);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(
+        file,
+        "{}\t{}\t{}\t{}",
+        score.difficulty, score.ticks, score.timestamp, score.name
+    )?;
+
+    Ok(is_new_record)
+}
+
+pub struct Leaderboard {
+    categories: Vec<String>,
+    selected: usize,
+    scores: Vec<Score>,
+}
+
+impl Leaderboard {
+    pub fn new(path: Option<&PathBuf>, current: String) -> Self {
+        let scores = path.map(load_scores).unwrap_or_default();
+
+        let mut categories: Vec<_> = PRESET_NAMES.iter().map(|x| x.to_string()).collect();
+        categories.extend(
+            PRESET_NAMES
+                .iter()
+                .map(|x| format!("{}{}", x, NO_GUESS_SUFFIX)),
+        );
+        for difficulty in scores.iter().map(|x| &x.difficulty).chain([&current]) {
+            if !categories.contains(difficulty) {
+                categories.push(difficulty.clone());
+            }
+        }
+
+        Leaderboard {
+            selected: categories.iter().position(|x| *x == current).unwrap_or(0),
+            categories,
+            scores,
+        }
+    }
+
+    pub fn render(&self, screen: &mut ScreenBuffer) {
+        let difficulty = &self.categories[self.selected];
+        screen.write(&format!("Best times - {}", difficulty));
+        screen.new_line();
+
+        let best = best_scores(&self.scores, difficulty, LEADERBOARD_SIZE);
+        if best.is_empty() {
+            screen.write("  No records yet");
+            screen.new_line();
+        }
+
+        if !best.is_empty() {
+            let mut table = Table::new(&["#", "Time", "Date", "Name"])
+                .align(0, Align::Right)
+                .align(1, Align::Right);
+            for (rank, score) in best.iter().enumerate() {
+                table.push_row(vec![
+                    format!("{}.", rank + 1),
+                    format!("{:.2}s", score.seconds()),
+                    score.date(),
+                    score.name.clone(),
+                ]);
+            }
+
+            let (_, y) = screen.cursor();
+            table.render(screen, Rect::new(2, y, table.width(), table.height()));
+            screen.new_line();
+        }
+
+        screen.new_line();
+        screen.write("Left/Right - Change difficulty, Esc (or B) - Back");
+    }
+
+    // Returns whether the leaderboard should be closed
+    pub fn process_key(&mut self, key: Key) -> bool {
+        match key {
+            Key::ArrowLeft | Key::Character('h') => {
+                self.selected = (self.selected + self.categories.len() - 1) % self.categories.len()
+            }
+            Key::ArrowRight | Key::Character('l') => {
+                self.selected = (self.selected + 1) % self.categories.len()
+            }
+            Key::Escape | Key::Return | Key::Character('b') | Key::Character('B') => return true,
+            _ => (),
+        }
+
+        false
+    }
+}
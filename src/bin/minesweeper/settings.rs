@@ -0,0 +1,99 @@
+use crate::game::{BLACK, YELLOW};
+use tui::key::Key;
+use tui::screen::{Rect, ScreenBuffer, Style};
+use tui::widget::{InputAction, Label, Panel, TextInput, Widget};
+
+const LABELS: [&str; 3] = ["Width ", "Height", "Mines "];
+const MAX_DIGITS: usize = 5;
+
+pub enum FormAction {
+    None,
+    Cancel,
+    Submit(usize, usize, usize),
+}
+
+pub struct SettingsForm {
+    fields: [TextInput; 3],
+    selected: usize,
+    error: Option<String>,
+}
+
+impl SettingsForm {
+    pub fn new(width: usize, height: usize, mines: usize) -> Self {
+        let field = |value: usize| {
+            TextInput::new(&value.to_string())
+                .max_length(MAX_DIGITS)
+                .filter(|c| c.is_ascii_digit())
+                .style(Style::new().fg(BLACK).bg(YELLOW))
+        };
+
+        let mut form = SettingsForm {
+            fields: [field(width), field(height), field(mines)],
+            selected: 0,
+            error: None,
+        };
+        form.select(0);
+        form
+    }
+
+    pub fn set_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    fn select(&mut self, selected: usize) {
+        self.selected = selected;
+        for (i, field) in self.fields.iter_mut().enumerate() {
+            field.set_focused(i == selected);
+        }
+    }
+
+    pub fn render(&self, screen: &mut ScreenBuffer) {
+        // "> Width : " and the field with room for the cursor
+        let panel_rect = Rect::new(0, 0, 2 + LABELS[0].len() + 2 + MAX_DIGITS + 1 + 2, 5);
+        let panel = Panel::new().title("Custom board");
+        panel.render(screen, panel_rect);
+        let inner = panel.inner(panel_rect);
+
+        for (i, (label, field)) in LABELS.iter().zip(&self.fields).enumerate() {
+            let y = inner.y + i;
+            let marker = if i == self.selected { ">" } else { " " };
+            let text = format!("{} {}: ", marker, label);
+            Label::new(&text).render(screen, Rect::new(inner.x, y, text.len(), 1));
+            field.render(
+                screen,
+                Rect::new(inner.x + text.len(), y, MAX_DIGITS + 1, 1),
+            );
+        }
+
+        screen.new_line();
+        if let Some(error) = &self.error {
+            screen.write(error);
+        }
+        screen.new_line();
+
+        screen.write("Up/Down/Tab - Select field, Enter - Start, Esc - Cancel");
+    }
+
+    pub fn process_key(&mut self, key: Key) -> FormAction {
+        match key {
+            Key::ArrowUp => self.select((self.selected + LABELS.len() - 1) % LABELS.len()),
+            Key::ArrowDown | Key::Tab => self.select((self.selected + 1) % LABELS.len()),
+            key => match self.fields[self.selected].process_key(&key) {
+                InputAction::None => (),
+                InputAction::Cancel => return FormAction::Cancel,
+                InputAction::Submit => {
+                    let values: Vec<_> =
+                        self.fields.iter().map(|x| x.value().parse().ok()).collect();
+                    match values.as_slice() {
+                        [Some(width), Some(height), Some(mines)] => {
+                            return FormAction::Submit(*width, *height, *mines)
+                        }
+                        _ => self.error = Some("All fields must be filled in".to_string()),
+                    }
+                }
+            },
+        }
+
+        FormAction::None
+    }
+}
@@ -0,0 +1,669 @@
+use crate::game::adjacent_cells_coord;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Tile {
+    Hidden,
+    Revealed(usize),
+    Mine,
+}
+
+pub struct Field {
+    pub width: usize,
+    pub height: usize,
+    pub mines: usize,
+    pub tiles: Vec<Tile>,
+}
+
+#[derive(Default)]
+pub struct Deduction {
+    pub safe: Vec<(usize, usize)>,
+    pub mines: Vec<(usize, usize)>,
+}
+
+impl Deduction {
+    pub fn is_empty(&self) -> bool {
+        self.safe.is_empty() && self.mines.is_empty()
+    }
+}
+
+struct Constraint {
+    center: (usize, usize),
+    cells: Vec<usize>,
+    mines: usize,
+}
+
+fn is_subset(a: &[usize], b: &[usize]) -> bool {
+    a.len() <= b.len() && a.iter().all(|x| b.binary_search(x).is_ok())
+}
+
+fn apply_constraints(constraints: &[Constraint], known: &mut [Option<bool>]) -> bool {
+    let mut is_progressed = false;
+
+    for constraint in constraints {
+        let is_mine = if constraint.mines == 0 {
+            false
+        } else if constraint.mines == constraint.cells.len() {
+            true
+        } else {
+            continue;
+        };
+
+        for &cell in &constraint.cells {
+            if known[cell].is_none() {
+                known[cell] = Some(is_mine);
+                is_progressed = true;
+            }
+        }
+    }
+
+    is_progressed
+}
+
+impl Field {
+    pub fn new(width: usize, height: usize, mines: usize) -> Self {
+        Field {
+            width,
+            height,
+            mines,
+            tiles: vec![Tile::Hidden; width * height],
+        }
+    }
+
+    pub fn tile_at(&self, x: usize, y: usize) -> Tile {
+        self.tiles[y * self.width + x]
+    }
+
+    pub fn set_tile(&mut self, x: usize, y: usize, tile: Tile) {
+        self.tiles[y * self.width + x] = tile;
+    }
+
+    fn constraints(&self, known: &[Option<bool>]) -> Vec<Constraint> {
+        let mut constraints = Vec::new();
+
+        for (i, tile) in self.tiles.iter().enumerate() {
+            let number = match tile {
+                Tile::Revealed(number) => *number,
+                _ => continue,
+            };
+
+            let (x, y) = (i % self.width, i / self.width);
+            let mut cells = Vec::new();
+            let mut known_mines = 0;
+
+            for (adj_x, adj_y) in adjacent_cells_coord(x, y, self.width, self.height) {
+                let adj = adj_y * self.width + adj_x;
+                match known[adj] {
+                    Some(true) => known_mines += 1,
+                    Some(false) => (),
+                    None => cells.push(adj),
+                }
+            }
+
+            if !cells.is_empty() {
+                cells.sort_unstable();
+                constraints.push(Constraint {
+                    center: (x, y),
+                    cells,
+                    mines: number.saturating_sub(known_mines),
+                });
+            }
+        }
+
+        constraints
+    }
+
+    fn subset_constraints(constraints: &[Constraint]) -> Vec<Constraint> {
+        let mut derived = Vec::new();
+
+        for a in constraints {
+            for b in constraints {
+                // Only constraints with centers within two cells of each other can share cells
+                if a.center.0.abs_diff(b.center.0) > 2
+                    || a.center.1.abs_diff(b.center.1) > 2
+                    || a.cells.len() >= b.cells.len()
+                    || !is_subset(&a.cells, &b.cells)
+                {
+                    continue;
+                }
+
+                let cells = b
+                    .cells
+                    .iter()
+                    .filter(|x| a.cells.binary_search(x).is_err())
+                    .copied()
+                    .collect();
+
+                derived.push(Constraint {
+                    center: b.center,
+                    cells,
+                    mines: b.mines.saturating_sub(a.mines),
+                });
+            }
+        }
+
+        derived
+    }
+
+    fn global_constraint(&self, known: &[Option<bool>]) -> Constraint {
+        let known_mines = known.iter().filter(|x| **x == Some(true)).count();
+
+        Constraint {
+            center: (0, 0),
+            cells: (0..known.len()).filter(|&i| known[i].is_none()).collect(),
+            mines: self.mines.saturating_sub(known_mines),
+        }
+    }
+
+    fn deduce_known(&self) -> Vec<Option<bool>> {
+        let mut known: Vec<_> = self
+            .tiles
+            .iter()
+            .map(|tile| match tile {
+                Tile::Hidden => None,
+                Tile::Revealed(_) => Some(false),
+                Tile::Mine => Some(true),
+            })
+            .collect();
+
+        loop {
+            let constraints = self.constraints(&known);
+            let is_progressed = apply_constraints(&constraints, &mut known)
+                || apply_constraints(&Field::subset_constraints(&constraints), &mut known)
+                || apply_constraints(&[self.global_constraint(&known)], &mut known);
+
+            if !is_progressed {
+                break;
+            }
+        }
+
+        known
+    }
+
+    fn to_deduction(&self, known: &[Option<bool>]) -> Deduction {
+        let mut deduction = Deduction::default();
+
+        for (i, (tile, known)) in self.tiles.iter().zip(known).enumerate() {
+            let coord = (i % self.width, i / self.width);
+            match (tile, known) {
+                (Tile::Hidden, Some(false)) => deduction.safe.push(coord),
+                (Tile::Hidden, Some(true)) => deduction.mines.push(coord),
+                _ => (),
+            }
+        }
+
+        deduction
+    }
+
+    pub fn deduce(&self) -> Deduction {
+        self.to_deduction(&self.deduce_known())
+    }
+
+    pub fn analyze(&self) -> Analysis {
+        let mut known = self.deduce_known();
+        let constraints = self.constraints(&known);
+        let remaining_mines = self
+            .mines
+            .saturating_sub(known.iter().filter(|x| **x == Some(true)).count());
+
+        let mut is_frontier = vec![false; self.tiles.len()];
+        constraints
+            .iter()
+            .flat_map(|c| &c.cells)
+            .for_each(|&cell| is_frontier[cell] = true);
+        let outside = (0..self.tiles.len())
+            .filter(|&i| known[i].is_none() && !is_frontier[i])
+            .count();
+
+        let enumerations: Option<Vec<_>> = components(&constraints)
+            .into_iter()
+            .map(|(cells, constraints)| Enumeration::new(cells, &constraints))
+            .collect();
+
+        let mut probabilities: Vec<_> = known
+            .iter()
+            .map(|x| x.map(|is_mine| if is_mine { 1.0 } else { 0.0 }))
+            .collect();
+
+        let combined = enumerations
+            .as_ref()
+            .and_then(|x| combine(x, outside, remaining_mines));
+
+        match (enumerations, combined) {
+            (Some(enumerations), Some((frontier, outside_probability))) => {
+                for (enumeration, cell_probabilities) in enumerations.iter().zip(frontier) {
+                    for (&cell, probability) in enumeration.cells.iter().zip(cell_probabilities) {
+                        probabilities[cell] = Some(probability);
+                    }
+                }
+
+                for i in 0..self.tiles.len() {
+                    if known[i].is_none() && !is_frontier[i] {
+                        probabilities[i] = Some(outside_probability);
+                    }
+                }
+
+                for (known, probability) in known.iter_mut().zip(&probabilities) {
+                    match probability {
+                        Some(x) if *x <= 0.0 => *known = Some(false),
+                        Some(x) if *x >= 1.0 - 1e-9 => *known = Some(true),
+                        _ => (),
+                    }
+                }
+            }
+            _ => {
+                // Too many configurations to enumerate: fall back to local mine densities
+                let unknown = known.iter().filter(|x| x.is_none()).count().max(1);
+                let density = remaining_mines as f64 / unknown as f64;
+
+                for (i, probability) in probabilities.iter_mut().enumerate() {
+                    if known[i].is_none() {
+                        *probability = Some(density);
+                    }
+                }
+
+                let mut local_densities = vec![0.0f64; self.tiles.len()];
+                for constraint in &constraints {
+                    let local = constraint.mines as f64 / constraint.cells.len() as f64;
+                    for &cell in &constraint.cells {
+                        local_densities[cell] = local_densities[cell].max(local);
+                        probabilities[cell] = Some(local_densities[cell]);
+                    }
+                }
+            }
+        }
+
+        for (i, tile) in self.tiles.iter().enumerate() {
+            if *tile != Tile::Hidden {
+                probabilities[i] = None;
+            }
+        }
+
+        Analysis {
+            deduction: self.to_deduction(&known),
+            probabilities,
+        }
+    }
+}
+
+pub struct Analysis {
+    pub deduction: Deduction,
+    pub probabilities: Vec<Option<f64>>,
+}
+
+fn components(constraints: &[Constraint]) -> Vec<(Vec<usize>, Vec<&Constraint>)> {
+    let mut component_of: Vec<Option<usize>> = vec![None; constraints.len()];
+    let mut result = Vec::new();
+
+    for start in 0..constraints.len() {
+        if component_of[start].is_some() {
+            continue;
+        }
+
+        let id = result.len();
+        let mut stack = vec![start];
+        let mut members = Vec::new();
+        component_of[start] = Some(id);
+
+        while let Some(i) = stack.pop() {
+            members.push(&constraints[i]);
+
+            for (j, other) in constraints.iter().enumerate() {
+                if component_of[j].is_none()
+                    && other.cells.iter().any(|x| constraints[i].cells.contains(x))
+                {
+                    component_of[j] = Some(id);
+                    stack.push(j);
+                }
+            }
+        }
+
+        let mut cells: Vec<_> = members.iter().flat_map(|c| c.cells.clone()).collect();
+        cells.sort_unstable();
+        cells.dedup();
+        result.push((cells, members));
+    }
+
+    result
+}
+
+const ENUMERATION_LIMIT: usize = 1 << 20;
+
+struct Enumeration {
+    cells: Vec<usize>,
+    // Number of mine configurations (and per-cell mine counts) keyed by the number of mines
+    configurations: Vec<f64>,
+    mine_counts: Vec<Vec<f64>>,
+}
+
+struct Search {
+    cell_constraints: Vec<Vec<usize>>,
+    required: Vec<usize>,
+    assigned_mines: Vec<usize>,
+    unassigned: Vec<usize>,
+    assignment: Vec<bool>,
+    nodes: usize,
+}
+
+impl Search {
+    fn run(&mut self, index: usize, enumeration: &mut Enumeration) -> bool {
+        self.nodes += 1;
+        if self.nodes > ENUMERATION_LIMIT {
+            return false;
+        }
+
+        if index == self.assignment.len() {
+            let mines = self.assignment.iter().filter(|x| **x).count();
+            enumeration.configurations[mines] += 1.0;
+            for (count, &is_mine) in enumeration.mine_counts[mines]
+                .iter_mut()
+                .zip(&self.assignment)
+            {
+                if is_mine {
+                    *count += 1.0;
+                }
+            }
+            return true;
+        }
+
+        for is_mine in [false, true] {
+            let is_valid = self.cell_constraints[index].iter().all(|&c| {
+                let mines = self.assigned_mines[c] + is_mine as usize;
+                mines <= self.required[c] && mines + self.unassigned[c] > self.required[c]
+            });
+
+            if !is_valid {
+                continue;
+            }
+
+            self.assignment[index] = is_mine;
+            for &c in &self.cell_constraints[index] {
+                self.assigned_mines[c] += is_mine as usize;
+                self.unassigned[c] -= 1;
+            }
+
+            let is_completed = self.run(index + 1, enumeration);
+
+            for &c in &self.cell_constraints[index] {
+                self.assigned_mines[c] -= is_mine as usize;
+                self.unassigned[c] += 1;
+            }
+
+            if !is_completed {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl Enumeration {
+    fn new(cells: Vec<usize>, constraints: &[&Constraint]) -> Option<Self> {
+        let cell_constraints = cells
+            .iter()
+            .map(|cell| {
+                (0..constraints.len())
+                    .filter(|&c| constraints[c].cells.contains(cell))
+                    .collect()
+            })
+            .collect();
+
+        let mut search = Search {
+            cell_constraints,
+            required: constraints.iter().map(|c| c.mines).collect(),
+            assigned_mines: vec![0; constraints.len()],
+            unassigned: constraints.iter().map(|c| c.cells.len()).collect(),
+            assignment: vec![false; cells.len()],
+            nodes: 0,
+        };
+
+        let mut enumeration = Enumeration {
+            configurations: vec![0.0; cells.len() + 1],
+            mine_counts: vec![vec![0.0; cells.len()]; cells.len() + 1],
+            cells,
+        };
+
+        if !search.run(0, &mut enumeration) {
+            return None;
+        }
+
+        // Scale the counts down to keep the products in `combine` within the range of f64
+        let max = enumeration
+            .configurations
+            .iter()
+            .copied()
+            .fold(0.0, f64::max);
+        if max > 0.0 {
+            enumeration
+                .configurations
+                .iter_mut()
+                .for_each(|x| *x /= max);
+            enumeration
+                .mine_counts
+                .iter_mut()
+                .flatten()
+                .for_each(|x| *x /= max);
+        }
+
+        Some(enumeration)
+    }
+}
+
+fn convolve(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut result = vec![0.0; a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            result[i + j] += x * y;
+        }
+    }
+    result
+}
+
+fn combine(
+    enumerations: &[Enumeration],
+    outside: usize,
+    remaining_mines: usize,
+) -> Option<(Vec<Vec<f64>>, f64)> {
+    // binomials[k] is proportional to the ways to place k mines outside the frontier
+    let mut binomials = vec![0.0; remaining_mines + 1];
+    let mut value = 1.0;
+    for (k, binomial) in binomials.iter_mut().enumerate().take(outside + 1) {
+        *binomial = value;
+        value *= (outside - k) as f64 / (k + 1) as f64;
+    }
+    let max = binomials.iter().copied().fold(0.0, f64::max);
+    binomials.iter_mut().for_each(|x| *x /= max);
+
+    let weight = |frontier_mines: usize| {
+        remaining_mines
+            .checked_sub(frontier_mines)
+            .map_or(0.0, |k| binomials[k])
+    };
+
+    let all = enumerations
+        .iter()
+        .fold(vec![1.0], |acc, e| convolve(&acc, &e.configurations));
+
+    let total: f64 = all.iter().enumerate().map(|(k, x)| x * weight(k)).sum();
+    if total <= 0.0 {
+        return None;
+    }
+
+    let outside_mines: f64 = all
+        .iter()
+        .enumerate()
+        .map(|(k, x)| x * weight(k) * remaining_mines.saturating_sub(k) as f64)
+        .sum();
+    let outside_probability = if outside > 0 {
+        outside_mines / total / outside as f64
+    } else {
+        0.0
+    };
+
+    let frontier = (0..enumerations.len())
+        .map(|j| {
+            let others = enumerations
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != j)
+                .fold(vec![1.0], |acc, (_, e)| convolve(&acc, &e.configurations));
+
+            let mut probabilities = vec![0.0; enumerations[j].cells.len()];
+            for (k, counts) in enumerations[j].mine_counts.iter().enumerate() {
+                let w: f64 = others
+                    .iter()
+                    .enumerate()
+                    .map(|(s, x)| x * weight(k + s))
+                    .sum();
+                for (probability, count) in probabilities.iter_mut().zip(counts) {
+                    *probability += count * w / total;
+                }
+            }
+            probabilities
+        })
+        .collect();
+
+    Some((frontier, outside_probability))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    fn field(width: usize, mines: usize, tiles: &[Tile]) -> Field {
+        let mut field = Field::new(width, tiles.len() / width, mines);
+        field.tiles = tiles.to_vec();
+        field
+    }
+
+    fn sorted(mut coords: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+        coords.sort_unstable();
+        coords
+    }
+
+    #[test]
+    fn one_two_one() {
+        use Tile::*;
+        let field = field(
+            3,
+            2,
+            &[
+                Hidden,
+                Hidden,
+                Hidden,
+                Revealed(1),
+                Revealed(2),
+                Revealed(1),
+            ],
+        );
+
+        let deduction = field.deduce();
+        assert_eq!(deduction.safe, [(1, 0)]);
+        assert_eq!(deduction.mines, [(0, 0), (2, 0)]);
+    }
+
+    #[test]
+    fn global_mine_count() {
+        use Tile::*;
+        // One of the two cells next to the 1 is a mine, which decides the cell far from it
+        let tiles = [Hidden, Revealed(1), Hidden, Hidden];
+
+        let analysis = field(4, 1, &tiles).analyze();
+        assert_eq!(analysis.deduction.safe, [(3, 0)]);
+        assert!(analysis.deduction.mines.is_empty());
+        assert_eq!(analysis.probabilities[0], Some(0.5));
+
+        let analysis = field(4, 2, &tiles).analyze();
+        assert!(analysis.deduction.safe.is_empty());
+        assert_eq!(analysis.deduction.mines, [(3, 0)]);
+    }
+
+    // Cells which are safe and mines in every layout matching the field
+    fn brute_force(field: &Field) -> Deduction {
+        let hidden: Vec<_> = (0..field.tiles.len())
+            .filter(|&i| field.tiles[i] == Tile::Hidden)
+            .collect();
+        let mut ever_mine = vec![false; field.tiles.len()];
+        let mut ever_safe = vec![false; field.tiles.len()];
+
+        for mask in 0u32..1 << hidden.len() {
+            if mask.count_ones() as usize != field.mines {
+                continue;
+            }
+
+            let mut is_mine = vec![false; field.tiles.len()];
+            for (bit, &cell) in hidden.iter().enumerate() {
+                is_mine[cell] = mask & 1 << bit != 0;
+            }
+
+            let is_consistent = (0..field.tiles.len()).all(|i| match field.tiles[i] {
+                Tile::Revealed(number) => {
+                    let (x, y) = (i % field.width, i / field.width);
+                    adjacent_cells_coord(x, y, field.width, field.height)
+                        .filter(|&(x, y)| is_mine[y * field.width + x])
+                        .count()
+                        == number
+                }
+                _ => true,
+            });
+
+            if is_consistent {
+                for &cell in &hidden {
+                    ever_mine[cell] |= is_mine[cell];
+                    ever_safe[cell] |= !is_mine[cell];
+                }
+            }
+        }
+
+        let coords = |cells: Vec<usize>| {
+            cells
+                .iter()
+                .map(|&i| (i % field.width, i / field.width))
+                .collect()
+        };
+        Deduction {
+            safe: coords(hidden.iter().copied().filter(|&i| !ever_mine[i]).collect()),
+            mines: coords(hidden.iter().copied().filter(|&i| !ever_safe[i]).collect()),
+        }
+    }
+
+    #[test]
+    fn analyze_matches_brute_force() {
+        let (width, height, mines) = (5, 4, 4);
+
+        for seed in 0..300 {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let mut is_mine = vec![false; width * height];
+            while is_mine.iter().filter(|x| **x).count() < mines {
+                is_mine[rng.gen_range(0..width * height)] = true;
+            }
+
+            let mut field = Field::new(width, height, mines);
+            for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
+                if !is_mine[y * width + x] && rng.gen_bool(0.5) {
+                    let number = adjacent_cells_coord(x, y, width, height)
+                        .filter(|&(x, y)| is_mine[y * width + x])
+                        .count();
+                    field.set_tile(x, y, Tile::Revealed(number));
+                }
+            }
+
+            let deduction = field.analyze().deduction;
+            let expected = brute_force(&field);
+            assert_eq!(
+                sorted(deduction.safe),
+                sorted(expected.safe),
+                "seed {}",
+                seed
+            );
+            assert_eq!(
+                sorted(deduction.mines),
+                sorted(expected.mines),
+                "seed {}",
+                seed
+            );
+        }
+    }
+}
@@ -3,9 +3,11 @@ use tui::key::*;
 use tui::rawmode::*;
 
 fn main() -> io::Result<()> {
-    let mut raw_mode = RawMode::new();
-    raw_mode.enable()?;
-    println!("{}", ENABLE_MOUSE);
+    let raw_mode = RawMode::enable()?;
+    println!(
+        "{}{}{}",
+        ENABLE_MOUSE, ENABLE_BRACKETED_PASTE, ENABLE_FOCUS_EVENTS
+    );
     let mut input = KeyInput::new(io::stdin());
     loop {
         let next = input.get_key()?;
@@ -14,7 +16,10 @@ fn main() -> io::Result<()> {
             break;
         }
     }
-    raw_mode.disable()?;
-    println!("{}", DISABLE_MOUSE);
+    drop(raw_mode);
+    println!(
+        "{}{}{}",
+        DISABLE_FOCUS_EVENTS, DISABLE_BRACKETED_PASTE, DISABLE_MOUSE
+    );
     Ok(())
 }
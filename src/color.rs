@@ -0,0 +1,192 @@
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+// Colors of the first 16 entries of the palette, as in xterm's defaults
+const NAMED_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+const NAMED: [Color; 16] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+    Color::BrightBlack,
+    Color::BrightRed,
+    Color::BrightGreen,
+    Color::BrightYellow,
+    Color::BrightBlue,
+    Color::BrightMagenta,
+    Color::BrightCyan,
+    Color::BrightWhite,
+];
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+fn nearest_cube_level(value: u8) -> usize {
+    (0..CUBE_LEVELS.len())
+        .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - value as i32).abs())
+        .unwrap()
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ColorSupport {
+    Basic,
+    Indexed,
+    TrueColor,
+}
+
+impl ColorSupport {
+    pub fn from_terminal_type(terminal_type: &str) -> Self {
+        let terminal_type = terminal_type.to_ascii_lowercase();
+
+        if ["truecolor", "24bit", "direct"]
+            .iter()
+            .any(|x| terminal_type.contains(x))
+        {
+            ColorSupport::TrueColor
+        } else if terminal_type.contains("256") {
+            ColorSupport::Indexed
+        } else {
+            ColorSupport::Basic
+        }
+    }
+
+    // COLORTERM is set by terminals which support 24-bit colors, whatever TERM is
+    pub fn from_env() -> Self {
+        match std::env::var("COLORTERM").as_deref() {
+            Ok("truecolor") | Ok("24bit") => ColorSupport::TrueColor,
+            _ => std::env::var("TERM")
+                .map(|x| ColorSupport::from_terminal_type(&x))
+                .unwrap_or(ColorSupport::Basic),
+        }
+    }
+}
+
+impl Color {
+    fn named_index(&self) -> Option<usize> {
+        NAMED.iter().position(|x| x == self)
+    }
+
+    pub fn to_rgb(&self) -> (u8, u8, u8) {
+        match *self {
+            Color::Rgb(r, g, b) => (r, g, b),
+            Color::Indexed(index) => {
+                let index = index as usize;
+                match index {
+                    0..=15 => NAMED_RGB[index],
+                    16..=231 => {
+                        let index = index - 16;
+                        (
+                            CUBE_LEVELS[index / 36],
+                            CUBE_LEVELS[index / 6 % 6],
+                            CUBE_LEVELS[index % 6],
+                        )
+                    }
+                    _ => {
+                        let level = (8 + (index - 232) * 10) as u8;
+                        (level, level, level)
+                    }
+                }
+            }
+            named => NAMED_RGB[named.named_index().unwrap()],
+        }
+    }
+
+    fn to_indexed(self) -> Color {
+        let (r, g, b) = match self {
+            Color::Rgb(r, g, b) => (r, g, b),
+            _ => return self,
+        };
+
+        let cube = [r, g, b].map(nearest_cube_level);
+        let cube_index = 16 + cube[0] * 36 + cube[1] * 6 + cube[2];
+
+        let average = (r as usize + g as usize + b as usize) / 3;
+        let gray_index = 232 + (average.saturating_sub(3) / 10).min(23);
+
+        [cube_index, gray_index]
+            .into_iter()
+            .map(|index| Color::Indexed(index as u8))
+            .min_by_key(|x| distance(x.to_rgb(), (r, g, b)))
+            .unwrap()
+    }
+
+    fn to_named(self) -> Color {
+        if self.named_index().is_some() {
+            return self;
+        }
+
+        let rgb = self.to_rgb();
+        NAMED
+            .into_iter()
+            .min_by_key(|x| distance(x.to_rgb(), rgb))
+            .unwrap()
+    }
+
+    pub fn downgrade(self, support: ColorSupport) -> Color {
+        match support {
+            ColorSupport::TrueColor => self,
+            ColorSupport::Indexed => self.to_indexed(),
+            ColorSupport::Basic => self.to_named(),
+        }
+    }
+
+    // Parameters of SGR, for the background if `is_background` is set
+    pub fn sgr_params(&self, is_background: bool, support: ColorSupport) -> String {
+        let offset = if is_background { 10 } else { 0 };
+
+        match self.downgrade(support) {
+            Color::Indexed(index) => format!("{};5;{}", 38 + offset, index),
+            Color::Rgb(r, g, b) => format!("{};2;{};{};{}", 38 + offset, r, g, b),
+            named => {
+                let index = named.named_index().unwrap();
+                let base = if index < 8 { 30 } else { 90 - 8 };
+                (base + index + offset).to_string()
+            }
+        }
+    }
+}
@@ -1,31 +1,114 @@
+use crate::color::ColorSupport;
 use crate::key::{Key, KeyInput};
 use crate::screen::{Screen, ScreenBuffer};
 use std::io::{Read, Write};
-use std::sync::mpsc::channel;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
 use std::thread;
 use std::time::{Duration, Instant};
 
-enum Event {
+pub const DEFAULT_TICK_RATE: usize = 60;
+
+// Ticks missed by more than this (e.g. while the process was stopped) are skipped
+const MAX_TICK_LAG: Duration = Duration::from_secs(1);
+
+pub(crate) enum Event {
     Tick,
     Key(Key),
+    Resize(usize, usize),
+    TerminalType(String),
+    ColorSupport(ColorSupport),
+    // The terminal modes are turned off before the process is stopped, and the sender is
+    // told when that is done
+    Suspend(Sender<()>),
+    Resume,
     Terminate,
 }
 
+pub(crate) type EventSender = Sender<std::io::Result<Event>>;
+
 pub trait Game {
     fn render(&self) -> ScreenBuffer;
 
     fn process_key(&mut self, key: Key);
-    fn tick(&mut self);
+    // Returns whether the screen has to be drawn again
+    fn tick(&mut self) -> bool;
+
+    // Ticks per second, or None when the game needs no ticks. It is checked after every event.
+    fn tick_rate(&self) -> Option<usize> {
+        Some(DEFAULT_TICK_RATE)
+    }
+
+    fn resize(&mut self, _width: usize, _height: usize) {}
+    fn set_terminal_type(&mut self, _terminal_type: &str) {}
+    fn quit(&mut self) {}
 }
 
-pub fn run_game<T, R, W>(mut game: T, read: R, mut write: W) -> std::io::Result<()>
+// Ticks are due at fixed points from the start, so that delays in handling them don't add up
+struct TickTimeline {
+    rate: Option<usize>,
+    start: Instant,
+    count: u64,
+}
+
+impl TickTimeline {
+    fn new() -> Self {
+        TickTimeline {
+            rate: None,
+            start: Instant::now(),
+            count: 0,
+        }
+    }
+
+    fn restart(&mut self) {
+        self.start = Instant::now();
+        self.count = 0;
+    }
+
+    // The time the next tick is due, if there are ticks at the rate
+    fn next_tick(&mut self, rate: Option<usize>) -> Option<Instant> {
+        let rate = rate.filter(|&x| x > 0);
+        if rate != self.rate {
+            self.rate = rate;
+            self.restart();
+        }
+
+        let due = |timeline: &TickTimeline| {
+            let nanos = (timeline.count + 1) * 1_000_000_000 / rate? as u64;
+            Some(timeline.start + Duration::from_nanos(nanos))
+        };
+
+        if due(self)? + MAX_TICK_LAG < Instant::now() {
+            self.restart();
+        }
+        due(self)
+    }
+}
+
+pub fn run_game<T, R, W>(game: T, read: R, write: W) -> std::io::Result<()>
 where
     T: Game,
     R: Read + Send + 'static,
     W: Write,
+{
+    run_game_with_events(game, write, |_| Ok(read))
+}
+
+// `make_input` may keep the sender to deliver events other than keys and ticks
+pub(crate) fn run_game_with_events<T, R, W, F>(
+    mut game: T,
+    mut write: W,
+    make_input: F,
+) -> std::io::Result<()>
+where
+    T: Game,
+    R: Read + Send + 'static,
+    W: Write,
+    F: FnOnce(EventSender) -> std::io::Result<R>,
 {
     let (sender, receiver) = channel();
 
+    let read = make_input(sender.clone())?;
+
     let key_sender = sender.clone();
 
     thread::spawn(move || {
@@ -50,33 +133,72 @@ where
         }
     });
 
-    let tick_sender = sender;
+    write!(write, "\x1b[2J\x1b[H")?;
+    let mut screen = Screen::new(write);
+    let mut ticks = TickTimeline::new();
+    let mut needs_render = true;
+    let mut is_suspended = false;
+    // Cleared when the screen is invalidated, so that it is drawn again
+    let mut last_frame: Option<ScreenBuffer> = None;
 
-    thread::spawn(move || {
-        let mut sleep_duration = Duration::from_nanos(1_000_000_000 / 60);
-        loop {
-            let now = Instant::now();
-            thread::sleep(sleep_duration);
-            if tick_sender.send(Ok(Event::Tick)).is_err() {
-                break;
+    loop {
+        if needs_render && !is_suspended {
+            // Most events, e.g. keys the game doesn't use, leave the frame as it was
+            let frame = game.render();
+            if last_frame.as_ref() != Some(&frame) {
+                screen.render(frame.clone())?;
+                last_frame = Some(frame);
             }
-            sleep_duration = Duration::from_nanos(2_000_000_000 / 60).saturating_sub(now.elapsed());
         }
-    });
+        needs_render = true;
 
-    write!(write, "\x1b[2J\x1b[H")?;
-    let mut screen = Screen::new(write);
+        // `sender` is kept until the end, so receiving never fails
+        let event = match ticks.next_tick(game.tick_rate()) {
+            Some(due) => match receiver.recv_timeout(due.saturating_duration_since(Instant::now()))
+            {
+                Err(RecvTimeoutError::Timeout) => Ok(Event::Tick),
+                result => result.unwrap(),
+            },
+            None => receiver.recv().unwrap(),
+        };
 
-    loop {
-        screen.render(game.render())?;
-        match receiver.recv().unwrap()? {
-            Event::Tick => game.tick(),
+        match event? {
+            Event::Tick => {
+                ticks.count += 1;
+                needs_render = game.tick();
+            }
             Event::Key(key) => game.process_key(key),
-            Event::Terminate => break,
+            Event::Resize(width, height) => {
+                game.resize(width, height);
+                screen.invalidate()?;
+                last_frame = None;
+            }
+            Event::TerminalType(terminal_type) => game.set_terminal_type(&terminal_type),
+            Event::ColorSupport(color_support) => {
+                screen.set_color_support(color_support);
+                screen.invalidate()?;
+                last_frame = None;
+            }
+            Event::Suspend(done) => {
+                screen.set_modes(false)?;
+                is_suspended = true;
+                let _ = done.send(());
+            }
+            Event::Resume => {
+                screen.set_modes(true)?;
+                screen.invalidate()?;
+                last_frame = None;
+                is_suspended = false;
+            }
+            Event::Terminate => {
+                game.quit();
+                break;
+            }
         }
     }
 
     std::mem::drop(receiver);
+    std::mem::drop(sender);
 
     Ok(())
 }
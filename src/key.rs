@@ -1,174 +1,628 @@
-use std::io::Read;
+use std::io::{Error, ErrorKind, Read};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum MouseButton {
     Left,
+    Middle,
     Right,
+    WheelUp,
+    WheelDown,
+    WheelLeft,
+    WheelRight,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub control: bool,
+}
+
+impl Modifiers {
+    pub const SHIFT: Modifiers = Modifiers {
+        shift: true,
+        alt: false,
+        control: false,
+    };
+    pub const ALT: Modifiers = Modifiers {
+        shift: false,
+        alt: true,
+        control: false,
+    };
+    pub const CONTROL: Modifiers = Modifiers {
+        shift: false,
+        alt: false,
+        control: true,
+    };
+
+    // Shift = 1, Alt = 2, Control = 4, as in xterm's modifier parameter minus one
+    pub fn from_bits(bits: usize) -> Self {
+        Modifiers {
+            shift: bits & 1 != 0,
+            alt: bits & 2 != 0,
+            control: bits & 4 != 0,
+        }
+    }
+
+    pub fn bits(&self) -> usize {
+        self.shift as usize | (self.alt as usize) << 1 | (self.control as usize) << 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits() == 0
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
 pub enum Key {
     ArrowUp,
     ArrowDown,
     ArrowRight,
     ArrowLeft,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
     Delete,
+    Backspace,
     Escape,
     Return,
     Tab,
+    Function(u8),
     Control(char),
     Character(char),
     Mousedown(MouseButton, usize, usize),
     Mouseup(MouseButton, usize, usize),
+    // Motion while the button is held
+    Mousedrag(MouseButton, usize, usize),
+    Paste(String),
+    FocusIn,
+    FocusOut,
+    Modified(Modifiers, Box<Key>),
+}
+
+impl Key {
+    pub fn with_modifiers(self, modifiers: Modifiers) -> Key {
+        if modifiers.is_empty() {
+            self
+        } else {
+            Key::Modified(modifiers, Box::new(self))
+        }
+    }
 }
 
 pub const ENABLE_MOUSE: &str = "\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h";
 pub const DISABLE_MOUSE: &str = "\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l";
 
-fn process_input(input: &mut KeyInput) -> std::io::Result<Option<Key>> {
-    let key = match input.next_char()? {
+pub const ENABLE_BRACKETED_PASTE: &str = "\x1b[?2004h";
+pub const DISABLE_BRACKETED_PASTE: &str = "\x1b[?2004l";
+pub const ENABLE_FOCUS_EVENTS: &str = "\x1b[?1004h";
+pub const DISABLE_FOCUS_EVENTS: &str = "\x1b[?1004l";
+
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+// How long to wait for the rest of a sequence before taking ESC as the Escape key
+const ESC_TIMEOUT: Duration = Duration::from_millis(50);
+const MAX_SEQUENCE_LENGTH: usize = 64;
+// A paste whose end marker doesn't come in time is delivered as far as it was received.
+// Longer pastes are delivered in parts of about this length.
+const PASTE_TIMEOUT: Duration = Duration::from_secs(1);
+const MAX_PASTE_LENGTH: usize = 1 << 16;
+
+enum Parsed {
+    Key(Key, usize),
+    // Bytes which don't form a key and are skipped
+    Invalid(usize),
+    // A part of a long paste, with the length of the text after the start marker. The
+    // marker is kept for the rest.
+    PastePart(Key, usize),
+    Incomplete,
+}
+
+// `last_button` is reported on release in the encodings which don't tell the button
+fn parse_key(bytes: &[u8], last_button: &MouseButton) -> Parsed {
+    let first = match bytes.first() {
+        Some(&first) => first,
+        None => return Parsed::Incomplete,
+    };
+
+    let key = match first {
         9 => Key::Tab,
         13 => Key::Return,
-        27 => {
-            let key = process_escape(input);
-            input.flush_buf();
-            return Ok(key);
-        }
-        127 => Key::Delete,
+        27 => return parse_escape(bytes, last_button),
+        127 => Key::Backspace,
 
         x @ 1..=31 => Key::Control((x + b'A' - 1) as char),
         x @ 32..=126 => Key::Character(x as char),
 
-        // UTF-8 multibytes characters
-        0b11000000..=0b11011111 => {
-            input.skip(1);
-            return Ok(None);
+        0b11000000..=0b11011111 => return parse_utf8(bytes, 2),
+        0b11100000..=0b11101111 => return parse_utf8(bytes, 3),
+        0b11110000..=0b11110111 => return parse_utf8(bytes, 4),
+
+        _ => return Parsed::Invalid(1),
+    };
+
+    Parsed::Key(key, 1)
+}
+
+fn parse_utf8(bytes: &[u8], length: usize) -> Parsed {
+    for i in 1..length {
+        match bytes.get(i) {
+            None => return Parsed::Incomplete,
+            // The byte which cannot continue the sequence is left for the next key
+            Some(c) if c & 0b11000000 != 0b10000000 => return Parsed::Invalid(i),
+            Some(_) => (),
         }
-        0b11100000..=0b11101111 => {
-            input.skip(2);
-            return Ok(None);
+    }
+
+    // Rejects overlong encodings and surrogates
+    match std::str::from_utf8(&bytes[..length]) {
+        Ok(x) => Parsed::Key(Key::Character(x.chars().next().unwrap()), length),
+        Err(_) => Parsed::Invalid(length),
+    }
+}
+
+fn parse_escape(bytes: &[u8], last_button: &MouseButton) -> Parsed {
+    match bytes.get(1) {
+        None => Parsed::Incomplete,
+        Some(b'[') => parse_csi(bytes, last_button),
+        Some(b'O') => parse_ss3(bytes),
+        Some(&c @ 32..=126) => {
+            Parsed::Key(Key::Character(c as char).with_modifiers(Modifiers::ALT), 2)
         }
-        0b11110000..=0b11110111 => {
-            input.skip(3);
-            return Ok(None);
+        // The following byte starts another key
+        Some(_) => Parsed::Key(Key::Escape, 1),
+    }
+}
+
+fn paste(content: &[u8]) -> Key {
+    Key::Paste(String::from_utf8_lossy(content).into_owned())
+}
+
+// What a sequence prefix means when nothing follows it in time
+fn parse_timed_out(bytes: &[u8]) -> Parsed {
+    if let Some(content) = bytes.strip_prefix(PASTE_START) {
+        return Parsed::Key(paste(content), bytes.len());
+    }
+
+    match bytes {
+        [27, c @ 32..=126, ..] => {
+            Parsed::Key(Key::Character(*c as char).with_modifiers(Modifiers::ALT), 2)
         }
+        [27, ..] => Parsed::Key(Key::Escape, 1),
+        _ => Parsed::Invalid(1),
+    }
+}
+
+fn parse_params(bytes: &[u8]) -> Vec<usize> {
+    if bytes.is_empty() {
+        return Vec::new();
+    }
+
+    bytes
+        .split(|&c| c == b';')
+        .map(|x| {
+            std::str::from_utf8(x)
+                .ok()
+                .and_then(|x| x.parse().ok())
+                .unwrap_or(0)
+        })
+        .collect()
+}
+
+// Modifiers are sent as the second parameter, e.g. `ESC [1;5A` for Ctrl-ArrowUp
+fn parse_modifiers(params: &[usize]) -> Modifiers {
+    let param = params.get(1).copied().unwrap_or(1);
+    Modifiers::from_bits(param.saturating_sub(1))
+}
 
-        _ => return Ok(None),
+fn letter_key(c: u8) -> Option<Key> {
+    let key = match c {
+        b'A' => Key::ArrowUp,
+        b'B' => Key::ArrowDown,
+        b'C' => Key::ArrowRight,
+        b'D' => Key::ArrowLeft,
+        b'H' => Key::Home,
+        b'F' => Key::End,
+        b'P'..=b'S' => Key::Function(c - b'P' + 1),
+        _ => return None,
     };
 
-    Ok(Some(key))
+    Some(key)
 }
 
-fn process_escape(input: &mut KeyInput) -> Option<Key> {
-    let key = match input.next_char_in_buf() {
-        None => Key::Escape,
-        Some(b'[') => match input.next_char_in_buf()? {
-            b'<' => {
-                let mut params_str = String::new();
-                while let Some(next) =
-                    input.next_char_in_buf_if(|c| (b'0'..=b'9').contains(&c) || c == b';')
-                {
-                    params_str.push(next as char);
-                }
+// VT220 style `ESC [ <number> ~`
+fn tilde_key(number: usize) -> Option<Key> {
+    let key = match number {
+        1 | 7 => Key::Home,
+        2 => Key::Insert,
+        3 => Key::Delete,
+        4 | 8 => Key::End,
+        5 => Key::PageUp,
+        6 => Key::PageDown,
+        11..=15 => Key::Function((number - 10) as u8),
+        17..=21 => Key::Function((number - 11) as u8),
+        23..=26 => Key::Function((number - 12) as u8),
+        28 | 29 => Key::Function((number - 13) as u8),
+        31..=34 => Key::Function((number - 14) as u8),
+        _ => return None,
+    };
 
-                let params = if params_str.is_empty() {
-                    Vec::new()
-                } else {
-                    params_str
-                        .split(';')
-                        .flat_map(|x| x.parse::<usize>())
-                        .collect()
-                };
+    Some(key)
+}
 
-                let cb = *params.get(0)?;
-                let cx = *params.get(1)?;
-                let cy = *params.get(2)?;
+// Button code of the xterm protocol, common to all encodings
+fn parse_mouse(
+    code: usize,
+    x: usize,
+    y: usize,
+    is_release: bool,
+    last_button: &MouseButton,
+) -> Option<Key> {
+    let modifiers = Modifiers::from_bits((code >> 2) & 0b111);
+    let is_motion = code & 32 != 0;
 
-                let button = match cb {
-                    0 => MouseButton::Left,
-                    2 => MouseButton::Right,
-                    _ => return None,
-                };
+    let button = match code & !0b111100 {
+        0 => MouseButton::Left,
+        1 => MouseButton::Middle,
+        2 => MouseButton::Right,
+        // Release without the button
+        3 if !is_motion => return Some(Key::Mouseup(last_button.clone(), x, y)),
+        64 => MouseButton::WheelUp,
+        65 => MouseButton::WheelDown,
+        66 => MouseButton::WheelLeft,
+        67 => MouseButton::WheelRight,
+        _ => return None,
+    };
+
+    let key = if is_motion {
+        Key::Mousedrag(button, x, y)
+    } else if is_release {
+        Key::Mouseup(button, x, y)
+    } else {
+        Key::Mousedown(button, x, y)
+    };
+
+    Some(key.with_modifiers(modifiers))
+}
+
+// `ESC [`, followed by parameter bytes (0x30-0x3F), intermediate bytes (0x20-0x2F) and a
+// final byte (0x40-0x7E)
+fn parse_csi(bytes: &[u8], last_button: &MouseButton) -> Parsed {
+    // Linux console sends F1-F5 as `ESC [ [ A-E`
+    if bytes.get(2) == Some(&b'[') {
+        return match bytes.get(3) {
+            None => Parsed::Incomplete,
+            Some(&c @ b'A'..=b'E') => Parsed::Key(Key::Function(c - b'A' + 1), 4),
+            Some(_) => Parsed::Invalid(3),
+        };
+    }
 
-                match input.next_char_in_buf()? {
-                    b'm' => Key::Mouseup(button, cx, cy),
-                    b'M' => Key::Mousedown(button, cx, cy),
-                    _ => return None,
+    // X10 mouse encoding, `ESC [ M` followed by three bytes offset by 32
+    if bytes.get(2) == Some(&b'M') {
+        return match bytes.get(3..6) {
+            None => Parsed::Incomplete,
+            Some(params) => {
+                let [code, x, y] = [0, 1, 2].map(|i| (params[i] as usize).saturating_sub(32));
+                match parse_mouse(code, x, y, false, last_button) {
+                    Some(key) => Parsed::Key(key, 6),
+                    None => Parsed::Invalid(6),
                 }
             }
-            b'A' => Key::ArrowUp,
-            b'B' => Key::ArrowDown,
-            b'C' => Key::ArrowRight,
-            b'D' => Key::ArrowLeft,
-            _ => return None,
+        };
+    }
+
+    if bytes.starts_with(PASTE_START) {
+        return parse_paste(bytes);
+    }
+
+    let mut end = 2;
+    while let Some(&c) = bytes.get(end) {
+        match c {
+            0x20..=0x3f => end += 1,
+            0x40..=0x7e => break,
+            // Not a part of the sequence; it is left for the next key
+            _ => return Parsed::Invalid(end),
+        }
+
+        if end > MAX_SEQUENCE_LENGTH {
+            return Parsed::Invalid(end);
+        }
+    }
+
+    let command = match bytes.get(end) {
+        Some(&c) => c,
+        None => return Parsed::Incomplete,
+    };
+    let length = end + 1;
+
+    let key = match &bytes[2..end] {
+        // SGR mouse encoding, `ESC [ < code ; x ; y M` or `m` on release
+        [b'<', params @ ..] => match (command, &parse_params(params)[..]) {
+            (b'M' | b'm', &[code, x, y]) => parse_mouse(code, x, y, command == b'm', last_button),
+            _ => None,
         },
-        _ => return None,
+        // urxvt (1015) mouse encoding, `ESC [ code ; x ; y M` where the code is offset by 32
+        params if command == b'M' => match parse_params(params)[..] {
+            [code, x, y] => parse_mouse(code.saturating_sub(32), x, y, false, last_button),
+            _ => None,
+        },
+        params if params.iter().all(|&c| c.is_ascii_digit() || c == b';') => {
+            let params = parse_params(params);
+            let key = match command {
+                b'~' => params.first().and_then(|&x| tilde_key(x)),
+                b'Z' => Some(Key::Tab.with_modifiers(Modifiers::SHIFT)),
+                b'I' if params.is_empty() => Some(Key::FocusIn),
+                b'O' if params.is_empty() => Some(Key::FocusOut),
+                c => letter_key(c),
+            };
+            key.map(|x| x.with_modifiers(parse_modifiers(&params)))
+        }
+        _ => None,
     };
 
-    Some(key)
+    match key {
+        Some(key) => Parsed::Key(key, length),
+        None => Parsed::Invalid(length),
+    }
 }
 
-pub struct KeyInput<'a> {
-    buf: [u8; 16],
-    buf_position: usize,
-    buf_size: usize,
-    source: Box<dyn Read + 'a>,
+fn parse_paste(bytes: &[u8]) -> Parsed {
+    let content = &bytes[PASTE_START.len()..];
+    match content
+        .windows(PASTE_END.len())
+        .position(|x| x == PASTE_END)
+    {
+        Some(end) => Parsed::Key(
+            paste(&content[..end]),
+            PASTE_START.len() + end + PASTE_END.len(),
+        ),
+        None if content.len() >= MAX_PASTE_LENGTH => {
+            // Not splitting a character or an end marker which is still coming. Continuation
+            // bytes beyond the longest character aren't valid UTF-8 anyway.
+            let limit = MAX_PASTE_LENGTH - PASTE_END.len();
+            let end = (limit.saturating_sub(3)..=limit)
+                .rev()
+                .find(|&x| x > 0 && content[x] & 0xc0 != 0x80)
+                .unwrap_or(limit);
+            Parsed::PastePart(paste(&content[..end]), end)
+        }
+        None => Parsed::Incomplete,
+    }
+}
+
+// SS3, sent by keypads in application mode and for F1-F4
+fn parse_ss3(bytes: &[u8]) -> Parsed {
+    let mut end = 2;
+    while bytes.get(end).is_some_and(|c| c.is_ascii_digit()) {
+        end += 1;
+    }
+
+    let command = match bytes.get(end) {
+        Some(&c) => c,
+        None => return Parsed::Incomplete,
+    };
+
+    let modifier = parse_params(&bytes[2..end]).first().copied().unwrap_or(1);
+    match letter_key(command) {
+        Some(key) => Parsed::Key(
+            key.with_modifiers(Modifiers::from_bits(modifier.saturating_sub(1))),
+            end + 1,
+        ),
+        None if command.is_ascii_graphic() => Parsed::Invalid(end + 1),
+        None => Parsed::Invalid(end),
+    }
 }
 
-impl<'a> KeyInput<'a> {
-    pub fn new(source: impl Read + 'a) -> Self {
+pub struct KeyInput {
+    pending: Vec<u8>,
+    last_button: MouseButton,
+    receiver: Receiver<std::io::Result<Vec<u8>>>,
+}
+
+impl KeyInput {
+    pub fn new(mut source: impl Read + Send + 'static) -> Self {
+        let (sender, receiver) = channel();
+
+        // Reads on a separate thread so that an incomplete sequence can time out
+        thread::spawn(move || {
+            let mut buf = [0; 1024];
+            loop {
+                let result = match source.read(&mut buf) {
+                    Ok(0) => Err(Error::from(ErrorKind::UnexpectedEof)),
+                    Ok(size) => Ok(buf[..size].to_vec()),
+                    Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                    Err(err) => Err(err),
+                };
+
+                let is_err = result.is_err();
+                if sender.send(result).is_err() || is_err {
+                    break;
+                }
+            }
+        });
+
         KeyInput {
-            buf: [0; 16],
-            buf_position: 0,
-            buf_size: 0,
-            source: Box::new(source),
+            pending: Vec::new(),
+            last_button: MouseButton::Left,
+            receiver,
         }
     }
 
-    fn next_char_in_buf(&mut self) -> Option<u8> {
-        if self.buf_size > self.buf_position {
-            self.buf_position += 1;
-            Some(self.buf[self.buf_position - 1])
+    fn receive(&mut self) -> std::io::Result<bool> {
+        // Pasted text may take a while, so it is given longer than the other sequences
+        let timeout = if self.pending.starts_with(PASTE_START) {
+            Some(PASTE_TIMEOUT)
+        } else if self.pending.first() == Some(&27) {
+            Some(ESC_TIMEOUT)
         } else {
             None
-        }
+        };
+
+        let received = match timeout {
+            Some(timeout) => match self.receiver.recv_timeout(timeout) {
+                Ok(received) => received,
+                Err(RecvTimeoutError::Timeout) => return Ok(false),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(Error::from(ErrorKind::UnexpectedEof))
+                }
+            },
+            None => self
+                .receiver
+                .recv()
+                .map_err(|_| Error::from(ErrorKind::UnexpectedEof))?,
+        };
+
+        self.pending.extend(received?);
+        Ok(true)
     }
 
-    fn next_char_in_buf_if<F: Fn(u8) -> bool>(&mut self, f: F) -> Option<u8> {
-        let head = *self.buf.get(self.buf_position)?;
-        if f(head) {
-            self.buf_position += 1;
-            Some(head)
-        } else {
-            None
+    pub fn get_key(&mut self) -> std::io::Result<Key> {
+        let mut is_timed_out = false;
+
+        loop {
+            let parsed = if is_timed_out {
+                parse_timed_out(&self.pending)
+            } else {
+                parse_key(&self.pending, &self.last_button)
+            };
+            is_timed_out = false;
+
+            match parsed {
+                Parsed::Key(key, length) => {
+                    self.pending.drain(..length);
+                    if let Key::Mousedown(
+                        button @ (MouseButton::Left | MouseButton::Middle | MouseButton::Right),
+                        _,
+                        _,
+                    ) = &key
+                    {
+                        self.last_button = button.clone();
+                    }
+                    return Ok(key);
+                }
+                Parsed::Invalid(length) => {
+                    self.pending.drain(..length);
+                }
+                Parsed::PastePart(key, length) => {
+                    self.pending
+                        .drain(PASTE_START.len()..PASTE_START.len() + length);
+                    return Ok(key);
+                }
+                Parsed::Incomplete => is_timed_out = !self.receive()?,
+            }
         }
     }
+}
 
-    fn next_char(&mut self) -> std::io::Result<u8> {
-        loop {
-            if let Some(next) = self.next_char_in_buf() {
-                return Ok(next);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::Sender;
+
+    // Gives one chunk per read, as a terminal would, and EOF once the sender is dropped
+    struct ChunkReader(Receiver<Vec<u8>>);
+
+    impl Read for ChunkReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.0.recv() {
+                Ok(chunk) => {
+                    buf[..chunk.len()].copy_from_slice(&chunk);
+                    Ok(chunk.len())
+                }
+                Err(_) => Ok(0),
             }
+        }
+    }
 
-            let size = self.source.read(&mut self.buf)?;
-            self.buf_size = size;
-            self.buf_position = 0;
+    fn input(chunks: &[&[u8]]) -> (KeyInput, Sender<Vec<u8>>) {
+        let (sender, receiver) = channel();
+        for chunk in chunks {
+            sender.send(chunk.to_vec()).unwrap();
         }
+        (KeyInput::new(ChunkReader(receiver)), sender)
     }
 
-    fn flush_buf(&mut self) {
-        self.buf_size = 0;
+    // The sender is kept until all keys are read, so that nothing times out as EOF
+    fn keys(chunks: &[&[u8]], count: usize) -> Vec<Key> {
+        let (mut input, _sender) = input(chunks);
+        (0..count).map(|_| input.get_key().unwrap()).collect()
     }
 
-    fn skip(&mut self, steps: usize) {
-        self.buf_position += steps;
+    #[test]
+    fn sequence_split_across_reads() {
+        assert_eq!(
+            keys(&[b"\x1b[", b"1;5", b"A"], 1),
+            [Key::ArrowUp.with_modifiers(Modifiers::CONTROL)]
+        );
     }
 
-    pub fn get_key(&mut self) -> std::io::Result<Key> {
-        loop {
-            if let Some(key) = process_input(self)? {
-                return Ok(key);
+    #[test]
+    fn keys_after_sequence_are_kept() {
+        assert_eq!(
+            keys(&[b"\x1b[Aab\x1b[B"], 4),
+            [
+                Key::ArrowUp,
+                Key::Character('a'),
+                Key::Character('b'),
+                Key::ArrowDown
+            ]
+        );
+    }
+
+    #[test]
+    fn lone_escape_times_out() {
+        assert_eq!(keys(&[b"\x1b"], 1), [Key::Escape]);
+    }
+
+    #[test]
+    fn invalid_utf8_is_skipped() {
+        assert_eq!(
+            keys(&[b"\xff\xe2(", "é".as_bytes()], 2),
+            [Key::Character('('), Key::Character('é')]
+        );
+    }
+
+    #[test]
+    fn escape_sequences() {
+        assert_eq!(
+            keys(&[b"\x1b[1;5A\x1b[3~\x1bOP"], 3),
+            [
+                Key::ArrowUp.with_modifiers(Modifiers::CONTROL),
+                Key::Delete,
+                Key::Function(1)
+            ]
+        );
+    }
+
+    #[test]
+    fn mouse_encodings() {
+        assert_eq!(
+            keys(&[b"\x1b[<0;10;5M\x1b[<0;10;5m\x1b[M *%\x1b[34;3;4M"], 4),
+            [
+                Key::Mousedown(MouseButton::Left, 10, 5),
+                Key::Mouseup(MouseButton::Left, 10, 5),
+                Key::Mousedown(MouseButton::Left, 10, 5),
+                Key::Mousedown(MouseButton::Right, 3, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn long_paste_of_continuation_bytes_is_split() {
+        let mut bytes = PASTE_START.to_vec();
+        bytes.extend([0x80; 70000]);
+
+        match parse_key(&bytes, &MouseButton::Left) {
+            Parsed::PastePart(Key::Paste(_), length) => {
+                assert_eq!(length, MAX_PASTE_LENGTH - PASTE_END.len())
             }
+            _ => panic!("expected a part of the paste"),
         }
     }
 }
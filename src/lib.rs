@@ -1,5 +1,9 @@
+pub mod color;
 pub mod game;
 pub mod key;
 pub mod rawmode;
+pub mod replay;
 pub mod runner;
 pub mod screen;
+mod telnet;
+pub mod widget;
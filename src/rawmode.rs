@@ -2,38 +2,48 @@ use std::io::{self, stdin};
 use std::os::unix::io::AsRawFd;
 use termios::{cfmakeraw, tcsetattr, Termios, TCSAFLUSH};
 
-#[derive(Default)]
+pub fn terminal_size() -> Option<(usize, usize)> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::ioctl(stdin().as_raw_fd(), libc::TIOCGWINSZ, &mut size) } != 0
+        || size.ws_col == 0
+        || size.ws_row == 0
+    {
+        return None;
+    }
+
+    Some((size.ws_col as usize, size.ws_row as usize))
+}
+
+// The terminal is in raw mode while the guard is alive, and restored when it is dropped
 pub struct RawMode {
-    origin: Option<Termios>,
+    origin: Termios,
+    raw: Termios,
 }
 
 impl RawMode {
-    pub fn new() -> Self {
-        RawMode::default()
+    pub fn enable() -> io::Result<Self> {
+        let origin = Termios::from_fd(stdin().as_raw_fd())?;
+        let mut raw = origin;
+        cfmakeraw(&mut raw);
+
+        let rawmode = RawMode { origin, raw };
+        rawmode.resume()?;
+        Ok(rawmode)
     }
 
-    pub fn enable(&mut self) -> io::Result<()> {
-        let stdin_fd = stdin().as_raw_fd();
-
-        if self.origin.is_some() {
-            return Ok(());
-        }
-
-        let mut term = Termios::from_fd(stdin_fd)?;
-        self.origin = Some(term);
-
-        cfmakeraw(&mut term);
-        tcsetattr(stdin_fd, TCSAFLUSH, &term)
+    // Restores the original mode until `resume` is called, e.g. while the process is stopped
+    pub fn suspend(&self) -> io::Result<()> {
+        tcsetattr(stdin().as_raw_fd(), TCSAFLUSH, &self.origin)
     }
 
-    pub fn disable(&mut self) -> io::Result<()> {
-        let stdin_fd = stdin().as_raw_fd();
-
-        if let Some(term) = &self.origin {
-            tcsetattr(stdin_fd, TCSAFLUSH, term)?;
-            self.origin = None;
-        }
+    pub fn resume(&self) -> io::Result<()> {
+        tcsetattr(stdin().as_raw_fd(), TCSAFLUSH, &self.raw)
+    }
+}
 
-        Ok(())
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = self.suspend();
     }
 }
@@ -0,0 +1,451 @@
+use crate::game::{Game, DEFAULT_TICK_RATE};
+use crate::key::{Key, Modifiers, MouseButton};
+use crate::screen::ScreenBuffer;
+use std::io::{self, BufRead, BufReader, Error, ErrorKind, Read, Write};
+use std::time::Instant;
+
+const HEADER: &str = "tui-replay 1";
+const SPEEDS: [f64; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+const DEFAULT_SPEED: usize = 2;
+
+#[derive(Clone)]
+pub enum Event {
+    Tick,
+    Key(Key),
+    Resize(usize, usize),
+}
+
+fn encode_button(button: &MouseButton) -> &'static str {
+    match button {
+        MouseButton::Left => "Left",
+        MouseButton::Middle => "Middle",
+        MouseButton::Right => "Right",
+        MouseButton::WheelUp => "WheelUp",
+        MouseButton::WheelDown => "WheelDown",
+        MouseButton::WheelLeft => "WheelLeft",
+        MouseButton::WheelRight => "WheelRight",
+    }
+}
+
+fn decode_button(button: &str) -> Option<MouseButton> {
+    match button {
+        "Left" => Some(MouseButton::Left),
+        "Middle" => Some(MouseButton::Middle),
+        "Right" => Some(MouseButton::Right),
+        "WheelUp" => Some(MouseButton::WheelUp),
+        "WheelDown" => Some(MouseButton::WheelDown),
+        "WheelLeft" => Some(MouseButton::WheelLeft),
+        "WheelRight" => Some(MouseButton::WheelRight),
+        _ => None,
+    }
+}
+
+fn encode_key(key: &Key) -> String {
+    match key {
+        Key::ArrowUp => "ArrowUp".to_string(),
+        Key::ArrowDown => "ArrowDown".to_string(),
+        Key::ArrowRight => "ArrowRight".to_string(),
+        Key::ArrowLeft => "ArrowLeft".to_string(),
+        Key::Home => "Home".to_string(),
+        Key::End => "End".to_string(),
+        Key::PageUp => "PageUp".to_string(),
+        Key::PageDown => "PageDown".to_string(),
+        Key::Insert => "Insert".to_string(),
+        Key::Delete => "Delete".to_string(),
+        Key::Backspace => "Backspace".to_string(),
+        Key::Escape => "Escape".to_string(),
+        Key::Return => "Return".to_string(),
+        Key::Tab => "Tab".to_string(),
+        Key::Function(n) => format!("Function {}", n),
+        Key::Control(c) => format!("Control {}", *c as u32),
+        Key::Character(c) => format!("Character {}", *c as u32),
+        Key::Mousedown(button, x, y) => format!("Mousedown {} {} {}", encode_button(button), x, y),
+        Key::Mouseup(button, x, y) => format!("Mouseup {} {} {}", encode_button(button), x, y),
+        Key::Mousedrag(button, x, y) => {
+            format!("Mousedrag {} {} {}", encode_button(button), x, y)
+        }
+        Key::Paste(text) => text.chars().fold("Paste".to_string(), |encoded, c| {
+            format!("{} {}", encoded, c as u32)
+        }),
+        Key::FocusIn => "FocusIn".to_string(),
+        Key::FocusOut => "FocusOut".to_string(),
+        Key::Modified(modifiers, key) => {
+            format!("Modified {} {}", modifiers.bits(), encode_key(key))
+        }
+    }
+}
+
+fn decode_key(key: &str) -> Option<Key> {
+    let parts: Vec<_> = key.split(' ').collect();
+    let char_at = |i: usize| parts.get(i)?.parse().ok().and_then(char::from_u32);
+    let number_at = |i: usize| parts.get(i)?.parse().ok();
+
+    let key = match parts[0] {
+        "ArrowUp" => Key::ArrowUp,
+        "ArrowDown" => Key::ArrowDown,
+        "ArrowRight" => Key::ArrowRight,
+        "ArrowLeft" => Key::ArrowLeft,
+        "Home" => Key::Home,
+        "End" => Key::End,
+        "PageUp" => Key::PageUp,
+        "PageDown" => Key::PageDown,
+        "Insert" => Key::Insert,
+        "Delete" => Key::Delete,
+        "Backspace" => Key::Backspace,
+        "Escape" => Key::Escape,
+        "Return" => Key::Return,
+        "Tab" => Key::Tab,
+        "Function" => Key::Function(parts.get(1)?.parse().ok()?),
+        "Control" => Key::Control(char_at(1)?),
+        "Character" => Key::Character(char_at(1)?),
+        "Mousedown" => Key::Mousedown(decode_button(parts.get(1)?)?, number_at(2)?, number_at(3)?),
+        "Mouseup" => Key::Mouseup(decode_button(parts.get(1)?)?, number_at(2)?, number_at(3)?),
+        "Mousedrag" => Key::Mousedrag(decode_button(parts.get(1)?)?, number_at(2)?, number_at(3)?),
+        "Paste" => Key::Paste(
+            parts[1..]
+                .iter()
+                .map(|x| x.parse().ok().and_then(char::from_u32))
+                .collect::<Option<_>>()?,
+        ),
+        "FocusIn" => Key::FocusIn,
+        "FocusOut" => Key::FocusOut,
+        "Modified" => {
+            let (_, rest) = key.split_once(' ')?;
+            let (bits, key) = rest.split_once(' ')?;
+            Key::Modified(
+                Modifiers::from_bits(bits.parse().ok()?),
+                Box::new(decode_key(key)?),
+            )
+        }
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+pub struct Recorder<G: Game, W: Write> {
+    game: G,
+    dest: W,
+    started_at: Instant,
+    pending_ticks: usize,
+}
+
+impl<G: Game, W: Write> Recorder<G, W> {
+    pub fn new(game: G, mut dest: W, metadata: &[(String, String)]) -> io::Result<Self> {
+        writeln!(dest, "{}", HEADER)?;
+        for (key, value) in metadata {
+            writeln!(dest, "@{} {}", key, value)?;
+        }
+
+        Ok(Recorder {
+            game,
+            dest,
+            started_at: Instant::now(),
+            pending_ticks: 0,
+        })
+    }
+
+    fn elapsed_millis(&self) -> u128 {
+        self.started_at.elapsed().as_millis()
+    }
+
+    // Consecutive ticks are stored as a single line
+    fn flush_ticks(&mut self) -> io::Result<()> {
+        if self.pending_ticks > 0 {
+            writeln!(
+                self.dest,
+                "{} T {}",
+                self.elapsed_millis(),
+                self.pending_ticks
+            )?;
+            self.pending_ticks = 0;
+        }
+
+        Ok(())
+    }
+
+    fn record_key(&mut self, key: &Key) -> io::Result<()> {
+        self.flush_ticks()?;
+        writeln!(self.dest, "{} K {}", self.elapsed_millis(), encode_key(key))?;
+        self.dest.flush()
+    }
+
+    fn record_resize(&mut self, width: usize, height: usize) -> io::Result<()> {
+        self.flush_ticks()?;
+        writeln!(
+            self.dest,
+            "{} R {} {}",
+            self.elapsed_millis(),
+            width,
+            height
+        )
+    }
+}
+
+impl<G: Game, W: Write> Drop for Recorder<G, W> {
+    fn drop(&mut self) {
+        let _ = self.flush_ticks();
+        let _ = self.dest.flush();
+    }
+}
+
+impl<G: Game, W: Write> Game for Recorder<G, W> {
+    fn render(&self) -> ScreenBuffer {
+        self.game.render()
+    }
+
+    fn process_key(&mut self, key: Key) {
+        let _ = self.record_key(&key);
+        self.game.process_key(key);
+    }
+
+    fn tick(&mut self) -> bool {
+        self.pending_ticks += 1;
+        self.game.tick()
+    }
+
+    fn tick_rate(&self) -> Option<usize> {
+        self.game.tick_rate()
+    }
+
+    fn resize(&mut self, width: usize, height: usize) {
+        let _ = self.record_resize(width, height);
+        self.game.resize(width, height);
+    }
+
+    fn set_terminal_type(&mut self, terminal_type: &str) {
+        self.game.set_terminal_type(terminal_type);
+    }
+
+    fn quit(&mut self) {
+        self.game.quit();
+    }
+}
+
+pub struct Replay {
+    metadata: Vec<(String, String)>,
+    // With the time in milliseconds since the recording started
+    events: Vec<(f64, Event)>,
+}
+
+impl Replay {
+    pub fn load(source: impl Read) -> io::Result<Self> {
+        let invalid = |line: usize| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid replay file (line {})", line + 1),
+            )
+        };
+
+        let mut lines = BufReader::new(source).lines();
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            return Err(invalid(0));
+        }
+
+        let mut replay = Replay {
+            metadata: Vec::new(),
+            events: Vec::new(),
+        };
+
+        let mut last_timestamp = 0.0;
+        for (i, line) in lines.enumerate() {
+            let line = line?;
+            let i = i + 1;
+
+            if let Some(entry) = line.strip_prefix('@') {
+                let (key, value) = entry.split_once(' ').unwrap_or((entry, ""));
+                replay.metadata.push((key.to_string(), value.to_string()));
+                continue;
+            }
+
+            let mut parts = line.splitn(3, ' ');
+            let timestamp: f64 = parts
+                .next()
+                .and_then(|x| x.parse::<u64>().ok())
+                .ok_or(invalid(i))? as f64;
+            let previous = std::mem::replace(&mut last_timestamp, timestamp);
+
+            match (parts.next(), parts.next()) {
+                // The ticks are written together when the next event happens, so they are
+                // spread over the time since the previous line
+                (Some("T"), Some(count)) => {
+                    let count: usize = count.parse().map_err(|_| invalid(i))?;
+                    replay.events.extend((1..=count).map(|n| {
+                        let time = previous + (timestamp - previous) * n as f64 / count as f64;
+                        (time, Event::Tick)
+                    }));
+                }
+                (Some("K"), Some(key)) => {
+                    let key = decode_key(key).ok_or(invalid(i))?;
+                    replay.events.push((timestamp, Event::Key(key)));
+                }
+                (Some("R"), Some(size)) => {
+                    let (width, height) = size
+                        .split_once(' ')
+                        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                        .ok_or(invalid(i))?;
+                    replay
+                        .events
+                        .push((timestamp, Event::Resize(width, height)));
+                }
+                _ => return Err(invalid(i)),
+            }
+        }
+
+        Ok(replay)
+    }
+
+    pub fn metadata(&self, key: &str) -> Option<&str> {
+        self.metadata
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+pub struct ReplayPlayer<G: Game> {
+    game: G,
+    events: Vec<(f64, Event)>,
+    position: usize,
+    speed: usize,
+    is_paused: bool,
+    // The time in the recording played up to, in milliseconds, and when it was updated
+    millis: f64,
+    updated_at: Instant,
+}
+
+impl<G: Game> ReplayPlayer<G> {
+    pub fn new(game: G, replay: Replay) -> Self {
+        ReplayPlayer {
+            game,
+            events: replay.events,
+            position: 0,
+            speed: DEFAULT_SPEED,
+            is_paused: false,
+            millis: 0.0,
+            updated_at: Instant::now(),
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = SPEEDS
+            .iter()
+            .position(|&x| x >= speed)
+            .unwrap_or(SPEEDS.len() - 1);
+    }
+
+    fn is_finished(&self) -> bool {
+        self.position >= self.events.len()
+    }
+
+    // Moves the time in the recording forward by the real time passed, at the current speed
+    fn update_time(&mut self) {
+        let now = Instant::now();
+        if !self.is_paused {
+            let elapsed = now.duration_since(self.updated_at).as_secs_f64() * 1000.0;
+            self.millis += elapsed * SPEEDS[self.speed];
+        }
+        self.updated_at = now;
+    }
+
+    fn play(&mut self, event: Event) {
+        match event {
+            Event::Tick => {
+                self.game.tick();
+            }
+            Event::Key(key) => self.game.process_key(key),
+            Event::Resize(width, height) => self.game.resize(width, height),
+        }
+    }
+
+    // Plays events until the next recorded key has been applied
+    fn step(&mut self) {
+        while let Some((millis, event)) = self.events.get(self.position).cloned() {
+            self.position += 1;
+            self.millis = millis;
+
+            let is_key = matches!(event, Event::Key(_));
+            self.play(event);
+            if is_key {
+                break;
+            }
+        }
+    }
+}
+
+impl<G: Game> Game for ReplayPlayer<G> {
+    fn render(&self) -> ScreenBuffer {
+        let mut screen = self.game.render();
+        screen.new_line();
+
+        let state = if self.is_finished() {
+            "Finished"
+        } else if self.is_paused {
+            "Paused"
+        } else {
+            "Playing"
+        };
+        screen.write(&format!(
+            "Replay: {} x{} ({}/{})",
+            state,
+            SPEEDS[self.speed],
+            self.position,
+            self.events.len(),
+        ));
+        screen.new_line();
+        screen.write("P - Pause, N - Step, +/- - Change speed, Ctrl-C - Quit");
+
+        screen
+    }
+
+    fn process_key(&mut self, key: Key) {
+        match key {
+            Key::Character('p') | Key::Character('P') | Key::Character(' ') => {
+                self.update_time();
+                self.is_paused ^= true;
+            }
+            Key::Character('n') | Key::Character('N') => {
+                self.is_paused = true;
+                self.step();
+            }
+            Key::Character('+') | Key::Character('=') => {
+                self.update_time();
+                self.speed = (self.speed + 1).min(SPEEDS.len() - 1);
+            }
+            Key::Character('-') => {
+                self.update_time();
+                self.speed = self.speed.saturating_sub(1);
+            }
+            _ => (),
+        }
+    }
+
+    // The position shown changes with every event played
+    fn tick(&mut self) -> bool {
+        if self.is_paused {
+            return false;
+        }
+
+        let position = self.position;
+
+        self.update_time();
+        while let Some((millis, event)) = self.events.get(self.position).cloned() {
+            if millis > self.millis {
+                break;
+            }
+            self.position += 1;
+            self.play(event);
+        }
+
+        self.position != position
+    }
+
+    // Events due are looked for on every tick, even while the game itself needs none
+    fn tick_rate(&self) -> Option<usize> {
+        if self.is_paused || self.is_finished() {
+            None
+        } else {
+            Some(DEFAULT_TICK_RATE)
+        }
+    }
+}
@@ -1,87 +1,194 @@
-use crate::game::{run_game, Game};
-use crate::key::{DISABLE_MOUSE, ENABLE_MOUSE};
-use crate::rawmode::RawMode;
-use std::io::{Read, Write};
-
-#[derive(Clone, Copy)]
-enum ParseState {
-    None,
-    WaitCommand,
-    WaitOption,
-    WaitSubnegtiation,
-}
+use crate::color::ColorSupport;
+use crate::game::{run_game_with_events, Event, Game};
+use crate::rawmode::{terminal_size, RawMode};
+use crate::screen::write_modes;
+use crate::telnet::TelnetStream;
+use nix::sys::signal::{kill, SigSet, SigmaskHow, Signal};
+use nix::unistd::Pid;
+use std::io::{stdout, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Weak};
+use std::thread;
 
-impl ParseState {
-    fn is_plain_char(&mut self, byte: u8) -> bool {
-        let (next_state, result) = match (*self, byte) {
-            (_, 255) => (ParseState::WaitCommand, false),
-            (ParseState::None, _) => (ParseState::None, true),
-            (ParseState::WaitCommand, 250) => (ParseState::WaitSubnegtiation, false),
-            (ParseState::WaitCommand, 251..=254) => (ParseState::WaitOption, false),
-            (ParseState::WaitCommand, _) => (ParseState::None, false),
-            (ParseState::WaitOption, _) => (ParseState::None, false),
-            (ParseState::WaitSubnegtiation, _) => (ParseState::WaitSubnegtiation, false),
-        };
-
-        *self = next_state;
-        result
-    }
-}
+const CTRL_Z: u8 = 0x1a;
 
-struct TelnetRead<R: Read> {
-    state: ParseState,
-    read: R,
+// The modes are turned off again when `run` returns, fails or panics. A panic is resumed
+// after that.
+fn with_modes<W, F>(write: &mut W, run: F) -> std::io::Result<()>
+where
+    W: Write,
+    F: FnOnce(&mut W) -> std::io::Result<()>,
+{
+    write_modes(write, true)?;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| run(write)));
+    let restored = write_modes(write, false);
+
+    match result {
+        Ok(result) => result.and(restored),
+        Err(payload) => panic::resume_unwind(payload),
+    }
 }
 
-impl<R: Read> TelnetRead<R> {
-    fn new(read: R) -> Self {
-        TelnetRead {
-            state: ParseState::None,
-            read,
+// The terminal is restored before the panic message is printed, so that it can be read. The
+// game's writer may be in use by the panicking thread, so the modes are written to stdout, the
+// terminal of `RawMode`. The previous hook is set again when `run` returns or panics.
+fn with_panic_hook<T>(rawmode: Weak<RawMode>, run: impl FnOnce() -> T) -> T {
+    let previous = Arc::new(panic::take_hook());
+    let chained = previous.clone();
+    panic::set_hook(Box::new(move |info| {
+        if let Some(rawmode) = rawmode.upgrade() {
+            let _ = rawmode.suspend();
+            let _ = write_modes(&mut stdout(), false);
         }
+        chained(info);
+    }));
+
+    let result = panic::catch_unwind(AssertUnwindSafe(run));
+
+    // Dropping this hook drops the other reference to the previous one
+    drop(panic::take_hook());
+    if let Ok(previous) = Arc::try_unwrap(previous) {
+        panic::set_hook(previous);
     }
+
+    result.unwrap_or_else(|payload| panic::resume_unwind(payload))
+}
+
+// Raw mode turns off the terminal's own handling of Ctrl-Z, so SIGTSTP is raised here instead
+struct SuspendKey<R> {
+    read: R,
 }
 
-impl<R: Read> Read for TelnetRead<R> {
+impl<R: Read> Read for SuspendKey<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        let size = self.read.read(buf)?;
-        let mut ptr = 0;
-        for i in 0..size {
-            if self.state.is_plain_char(buf[i]) {
-                buf.swap(i, ptr);
-                ptr += 1;
+        loop {
+            let size = self.read.read(buf)?;
+            let kept: Vec<u8> = buf[..size]
+                .iter()
+                .copied()
+                .filter(|&x| x != CTRL_Z)
+                .collect();
+            if kept.len() < size {
+                let _ = kill(Pid::this(), Signal::SIGTSTP);
             }
-        }
 
-        Ok(ptr)
+            // Reading nothing means EOF, so it is only returned when nothing was read
+            if !kept.is_empty() || size == 0 {
+                buf[..kept.len()].copy_from_slice(&kept);
+                return Ok(kept.len());
+            }
+        }
     }
 }
 
-pub fn run_game_on_telnet<G, R, W>(game: G, read: R, mut write: W) -> std::io::Result<()>
+pub fn run_game_on_telnet<G, S, W>(game: G, stream: S, mut write: W) -> std::io::Result<()>
+where
+    G: Game,
+    S: Read + Write + Send + 'static,
+    W: Write,
+{
+    with_modes(&mut write, |write| {
+        run_game_with_events(game, write, |events| {
+            let mut stream = TelnetStream::new(stream, events);
+            stream.negotiate()?;
+            Ok(stream)
+        })
+    })
+}
+
+pub fn run_game_on_tty<G, R, W>(game: G, read: R, write: W) -> std::io::Result<()>
 where
     G: Game,
     R: Read + Send + 'static,
     W: Write,
 {
-    write.write_all(&[255, 253, 34, 255, 250, 34, 1, 0, 255, 240, 255, 251, 1])?;
-    write.write_all(ENABLE_MOUSE.as_bytes())?;
-    write.flush()?;
-    let result = run_game(game, TelnetRead::new(read), &mut write);
-    write.write_all(DISABLE_MOUSE.as_bytes())?;
-    result
+    // The signals are blocked here (and in every thread spawned from here) and received by
+    // sigwait until the game returns
+    let mut signals = SigSet::empty();
+    for signal in [
+        Signal::SIGWINCH,
+        Signal::SIGTERM,
+        Signal::SIGHUP,
+        Signal::SIGINT,
+        Signal::SIGTSTP,
+        Signal::SIGCONT,
+    ] {
+        signals.add(signal);
+    }
+    let previous_mask = signals.thread_swap_mask(SigmaskHow::SIG_BLOCK)?;
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        run_with_signals(game, read, write, signals)
+    }));
+    let restored = previous_mask
+        .thread_set_mask()
+        .map_err(std::io::Error::from);
+
+    match result {
+        Ok(result) => result.and(restored),
+        Err(payload) => panic::resume_unwind(payload),
+    }
 }
 
-pub fn run_game_on_tty<G, R, W>(game: G, read: R, mut write: W) -> std::io::Result<()>
+fn run_with_signals<G, R, W>(game: G, read: R, mut write: W, signals: SigSet) -> std::io::Result<()>
 where
     G: Game,
     R: Read + Send + 'static,
     W: Write,
 {
-    let mut rawmode = RawMode::new();
-    write.write_all(ENABLE_MOUSE.as_bytes())?;
-    rawmode.enable()?;
-    let result = run_game(game, read, &mut write);
-    rawmode.disable()?;
-    write.write_all(DISABLE_MOUSE.as_bytes())?;
-    result
+    let rawmode = Arc::new(RawMode::enable()?);
+
+    with_panic_hook(Arc::downgrade(&rawmode), || {
+        with_modes(&mut write, |write| {
+            run_game_with_events(game, write, |events| {
+                if let Ok(terminal_type) = std::env::var("TERM") {
+                    let _ = events.send(Ok(Event::TerminalType(terminal_type)));
+                }
+                let _ = events.send(Ok(Event::ColorSupport(ColorSupport::from_env())));
+
+                if let Some((width, height)) = terminal_size() {
+                    let _ = events.send(Ok(Event::Resize(width, height)));
+                }
+
+                let rawmode = Arc::downgrade(&rawmode);
+                thread::spawn(move || loop {
+                    let event = match signals.wait() {
+                        Ok(Signal::SIGWINCH) => match terminal_size() {
+                            Some((width, height)) => Event::Resize(width, height),
+                            None => continue,
+                        },
+                        // The game loop turns the modes off, as it is the only writer to the
+                        // terminal. SIGCONT follows when the process is continued.
+                        Ok(Signal::SIGTSTP) => {
+                            let (done, wait_done) = channel();
+                            if events.send(Ok(Event::Suspend(done))).is_err() {
+                                break;
+                            }
+                            let _ = wait_done.recv();
+                            if let Some(rawmode) = rawmode.upgrade() {
+                                let _ = rawmode.suspend();
+                            }
+                            let _ = kill(Pid::this(), Signal::SIGSTOP);
+                            continue;
+                        }
+                        Ok(Signal::SIGCONT) => {
+                            if let Some(rawmode) = rawmode.upgrade() {
+                                let _ = rawmode.resume();
+                            }
+                            Event::Resume
+                        }
+                        Ok(_) => Event::Terminate,
+                        Err(_) => continue,
+                    };
+
+                    if events.send(Ok(event)).is_err() {
+                        break;
+                    }
+                });
+
+                Ok(SuspendKey { read })
+            })
+        })
+    })
 }
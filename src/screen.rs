@@ -1,35 +1,283 @@
+use crate::color::{Color, ColorSupport};
+use crate::key::{
+    DISABLE_BRACKETED_PASTE, DISABLE_FOCUS_EVENTS, DISABLE_MOUSE, ENABLE_BRACKETED_PASTE,
+    ENABLE_FOCUS_EVENTS, ENABLE_MOUSE,
+};
 use std::io::{BufWriter, Write};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-#[derive(PartialEq, Clone, Copy)]
+pub const ENTER_ALTERNATE_SCREEN: &str = "\x1b[?1049h";
+pub const LEAVE_ALTERNATE_SCREEN: &str = "\x1b[?1049l";
+pub const HIDE_CURSOR: &str = "\x1b[?25l";
+pub const SHOW_CURSOR: &str = "\x1b[?25h";
+pub const RESET_STYLE: &str = "\x1b[0m";
+
+// The terminal modes games are played in
+pub(crate) fn write_modes<W: Write>(write: &mut W, enable: bool) -> std::io::Result<()> {
+    let modes: &[&str] = if enable {
+        &[
+            ENTER_ALTERNATE_SCREEN,
+            HIDE_CURSOR,
+            ENABLE_MOUSE,
+            ENABLE_BRACKETED_PASTE,
+            ENABLE_FOCUS_EVENTS,
+        ]
+    } else {
+        &[
+            DISABLE_FOCUS_EVENTS,
+            DISABLE_BRACKETED_PASTE,
+            DISABLE_MOUSE,
+            RESET_STYLE,
+            SHOW_CURSOR,
+            LEAVE_ALTERNATE_SCREEN,
+        ]
+    };
+
+    for mode in modes {
+        write.write_all(mode.as_bytes())?;
+    }
+
+    write.flush()
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub blink: bool,
+    pub reverse: bool,
+    pub strikethrough: bool,
+}
+
+impl Style {
+    pub fn new() -> Self {
+        Style::default()
+    }
+
+    pub fn fg(self, color: Color) -> Self {
+        Style {
+            fg: Some(color),
+            ..self
+        }
+    }
+
+    pub fn bg(self, color: Color) -> Self {
+        Style {
+            bg: Some(color),
+            ..self
+        }
+    }
+
+    pub fn bold(self) -> Self {
+        Style { bold: true, ..self }
+    }
+
+    pub fn dim(self) -> Self {
+        Style { dim: true, ..self }
+    }
+
+    pub fn italic(self) -> Self {
+        Style {
+            italic: true,
+            ..self
+        }
+    }
+
+    pub fn underline(self) -> Self {
+        Style {
+            underline: true,
+            ..self
+        }
+    }
+
+    pub fn blink(self) -> Self {
+        Style {
+            blink: true,
+            ..self
+        }
+    }
+
+    pub fn reverse(self) -> Self {
+        Style {
+            reverse: true,
+            ..self
+        }
+    }
+
+    pub fn strikethrough(self) -> Self {
+        Style {
+            strikethrough: true,
+            ..self
+        }
+    }
+
+    // Parameters of the SGR sequence, only for the attributes which are set
+    fn sgr_params(&self, support: ColorSupport) -> Vec<String> {
+        let attributes = [
+            (self.bold, 1),
+            (self.dim, 2),
+            (self.italic, 3),
+            (self.underline, 4),
+            (self.blink, 5),
+            (self.reverse, 7),
+            (self.strikethrough, 9),
+        ];
+
+        let mut params: Vec<_> = attributes
+            .iter()
+            .filter(|(is_set, _)| *is_set)
+            .map(|(_, code)| code.to_string())
+            .collect();
+        params.extend(self.fg.map(|x| x.sgr_params(false, support)));
+        params.extend(self.bg.map(|x| x.sgr_params(true, support)));
+        params
+    }
+
+    // The shortest SGR sequence which changes the style from `self` to `to`
+    fn transition(&self, to: &Style, support: ColorSupport) -> String {
+        let is_removed = |from: bool, to: bool| from && !to;
+        let resets = is_removed(self.fg.is_some(), to.fg.is_some())
+            || is_removed(self.bg.is_some(), to.bg.is_some())
+            || is_removed(self.bold, to.bold)
+            || is_removed(self.dim, to.dim)
+            || is_removed(self.italic, to.italic)
+            || is_removed(self.underline, to.underline)
+            || is_removed(self.blink, to.blink)
+            || is_removed(self.reverse, to.reverse)
+            || is_removed(self.strikethrough, to.strikethrough);
+
+        let params = if resets {
+            let mut params = vec!["0".to_string()];
+            params.extend(to.sgr_params(support));
+            params
+        } else {
+            let added = Style {
+                fg: to.fg.filter(|_| to.fg != self.fg),
+                bg: to.bg.filter(|_| to.bg != self.bg),
+                bold: to.bold && !self.bold,
+                dim: to.dim && !self.dim,
+                italic: to.italic && !self.italic,
+                underline: to.underline && !self.underline,
+                blink: to.blink && !self.blink,
+                reverse: to.reverse && !self.reverse,
+                strikethrough: to.strikethrough && !self.strikethrough,
+            };
+            added.sgr_params(support)
+        };
+
+        if params.is_empty() {
+            String::new()
+        } else {
+            format!("\x1b[{}m", params.join(";"))
+        }
+    }
+}
+
+// A cell of the screen. A double-width grapheme is followed by a continuation cell, which
+// has an empty grapheme and is drawn together with it.
+#[derive(PartialEq, Clone)]
 struct Character {
-    character: char,
-    color: Option<(usize, usize)>,
+    grapheme: String,
+    style: Style,
+    // Blank filling the gap before a cell put further right; transparent when overlaid
+    is_padding: bool,
 }
 
 impl Character {
-    fn new(character: char, color: Option<(usize, usize)>) -> Self {
-        Character { character, color }
+    fn new(grapheme: &str, style: Style) -> Self {
+        Character {
+            grapheme: grapheme.to_string(),
+            style,
+            is_padding: false,
+        }
+    }
+
+    fn padding() -> Self {
+        Character {
+            is_padding: true,
+            ..Character::new(" ", Style::new())
+        }
+    }
+
+    fn is_continuation(&self) -> bool {
+        self.grapheme.is_empty()
     }
 }
 
-fn write_char(dest: &mut impl Write, x: usize, y: usize, c: Character) -> std::io::Result<()> {
-    if let Some((fg_color, bg_color)) = c.color {
-        write!(
-            dest,
-            "\x1b[{};{}H\x1b[{};{}m{}\x1b[0m",
-            y + 1,
-            x + 1,
-            fg_color,
-            bg_color,
-            c.character,
-        )
-    } else {
-        write!(dest, "\x1b[{};{}H{}", y + 1, x + 1, c.character,)
+pub fn display_width(text: &str) -> usize {
+    text.graphemes(true).map(grapheme_width).sum()
+}
+
+fn grapheme_width(grapheme: &str) -> usize {
+    grapheme.width().clamp(1, 2)
+}
+
+// Tracks the cursor and the current style while a frame is written
+struct Output<W: Write> {
+    dest: W,
+    cursor: Option<(usize, usize)>,
+    style: Style,
+    color_support: ColorSupport,
+}
+
+impl<W: Write> Output<W> {
+    fn set_style(&mut self, style: &Style) -> std::io::Result<()> {
+        let sequence = self.style.transition(style, self.color_support);
+        self.style = *style;
+        self.dest.write_all(sequence.as_bytes())
+    }
+
+    // Unchanged cells in between are written again if it is shorter than moving over them
+    fn move_to(&mut self, x: usize, y: usize, line: &[Character]) -> std::io::Result<()> {
+        match self.cursor {
+            Some((cursor_x, cursor_y)) if cursor_y == y && cursor_x == x => return Ok(()),
+            Some((cursor_x, cursor_y)) if cursor_y == y && cursor_x < x => {
+                let skipped = &line[cursor_x..x];
+                let movement = format!("\x1b[{}C", x - cursor_x);
+                let rewritten_length: usize = skipped.iter().map(|c| c.grapheme.len()).sum();
+
+                if skipped.iter().all(|c| c.style == self.style)
+                    && rewritten_length <= movement.len()
+                {
+                    for c in skipped {
+                        self.dest.write_all(c.grapheme.as_bytes())?;
+                    }
+                } else {
+                    self.dest.write_all(movement.as_bytes())?;
+                }
+            }
+            _ => write!(self.dest, "\x1b[{};{}H", y + 1, x + 1)?,
+        }
+
+        self.cursor = Some((x, y));
+        Ok(())
+    }
+
+    fn write_char(&mut self, x: usize, y: usize, line: &[Character]) -> std::io::Result<()> {
+        let c = &line[x];
+        self.move_to(x, y, line)?;
+        self.set_style(&c.style)?;
+        self.dest.write_all(c.grapheme.as_bytes())?;
+        self.cursor = Some((x + grapheme_width(&c.grapheme), y));
+        Ok(())
+    }
+
+    fn clear_line_from(&mut self, x: usize, y: usize, line: &[Character]) -> std::io::Result<()> {
+        self.move_to(x, y, line)?;
+        // Erased cells are filled with the background color of the current style
+        self.set_style(&Style::new())?;
+        self.dest.write_all(b"\x1b[K")
     }
 }
 
 pub struct Screen<'a> {
     characters: Vec<Vec<Character>>,
+    color_support: ColorSupport,
     dest: Box<dyn Write + 'a>,
 }
 
@@ -37,83 +285,389 @@ impl<'a> Screen<'a> {
     pub fn new(dest: impl Write + 'a) -> Self {
         Screen {
             characters: vec![vec![]],
+            color_support: ColorSupport::Basic,
             dest: Box::new(dest),
         }
     }
 
+    // Characters already on the screen are not redrawn until `invalidate` is called
+    pub fn set_color_support(&mut self, color_support: ColorSupport) {
+        self.color_support = color_support;
+    }
+
+    // Used to give the terminal back while the process is stopped
+    pub(crate) fn set_modes(&mut self, enable: bool) -> std::io::Result<()> {
+        write_modes(&mut self.dest, enable)
+    }
+
+    pub fn invalidate(&mut self) -> std::io::Result<()> {
+        self.characters = vec![vec![]];
+        write!(self.dest, "\x1b[2J")
+    }
+
     pub fn render(&mut self, new: ScreenBuffer) -> std::io::Result<()> {
-        let mut stream = BufWriter::new(&mut self.dest);
+        let mut output = Output {
+            dest: BufWriter::new(&mut self.dest),
+            cursor: None,
+            style: Style::new(),
+            color_support: self.color_support,
+        };
 
-        if self.characters.len() < new.characters.len() {
-            self.characters.resize_with(new.characters.len(), Vec::new);
-        }
+        let height = self.characters.len().max(new.characters.len());
+        self.characters.resize_with(height, Vec::new);
+
+        for (y, old_line) in self.characters.iter_mut().enumerate() {
+            let new_line = new.characters.get(y).map_or(&[][..], |x| &x[..]);
 
-        for y in 0.. {
-            if self.characters.len().max(new.characters.len()) <= y {
-                break;
+            for (x, c) in new_line.iter().enumerate() {
+                // Continuation cells are drawn with the preceding double-width grapheme
+                if !c.is_continuation() && old_line.get(x) != Some(c) {
+                    output.write_char(x, y, new_line)?;
+                }
             }
 
-            for x in 0.. {
-                let old_char = self.characters.get(y).and_then(|l| l.get(x));
-                let new_char = new.characters.get(y).and_then(|l| l.get(x));
+            if old_line.len() > new_line.len() {
+                output.clear_line_from(new_line.len(), y, new_line)?;
+            }
 
-                match (old_char, new_char) {
-                    (Some(old), Some(new)) => {
-                        if old != new {
-                            self.characters[y][x] = *new;
-                            write_char(&mut stream, x, y, *new)?;
-                        }
-                    }
-                    (None, Some(new)) => {
-                        self.characters[y].push(*new);
-                        write_char(&mut stream, x, y, *new)?;
-                    }
-                    (Some(_), None) => {
-                        let left_chars = self.characters[y].len() - x;
-                        self.characters[y].truncate(x);
-                        write!(stream, "\x1b[{};{}H", y + 1, x + 1)?;
-                        for _ in 0..left_chars {
-                            write!(stream, " ")?;
-                        }
-                        break;
-                    }
-                    (None, None) => break,
-                }
+            if old_line[..] != new_line[..] {
+                *old_line = new_line.to_vec();
             }
         }
-        write!(stream, "\x1b[{};1H", self.characters.len() + 1)?;
-        stream.flush()
+
+        output.set_style(&Style::new())?;
+        write!(output.dest, "\x1b[{};1H", self.characters.len() + 1)?;
+        output.dest.flush()
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    // A rectangle of the size placed at the center of `self`
+    pub fn centered(&self, width: usize, height: usize) -> Rect {
+        let width = width.min(self.width);
+        let height = height.min(self.height);
+        Rect::new(
+            self.x + (self.width - width) / 2,
+            self.y + (self.height - height) / 2,
+            width,
+            height,
+        )
+    }
+
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+        Rect::new(x, y, right.saturating_sub(x), bottom.saturating_sub(y))
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
     }
 }
 
+#[derive(PartialEq, Clone)]
 pub struct ScreenBuffer {
     characters: Vec<Vec<Character>>,
+    clips: Vec<Rect>,
 }
 
 impl ScreenBuffer {
     pub fn new() -> Self {
         ScreenBuffer {
             characters: vec![vec![]],
+            clips: Vec::new(),
         }
     }
 
-    pub fn write_color(&mut self, chars: &str, fg_color: usize, bg_color: usize) {
-        let line = self.characters.last_mut().unwrap();
+    // Width of the longest line and the number of lines
+    pub fn size(&self) -> (usize, usize) {
+        let width = self.characters.iter().map(Vec::len).max().unwrap_or(0);
+        (width, self.characters.len())
+    }
 
-        for c in chars.chars() {
-            line.push(Character::new(c, Some((fg_color, bg_color + 10))));
+    // The position `write` continues from
+    pub fn cursor(&self) -> (usize, usize) {
+        let y = self.characters.len() - 1;
+        (self.characters[y].len(), y)
+    }
+
+    // Drawing at a position is restricted to the rectangle (and the ones pushed before it)
+    // until `pop_clip` is called. `write` and its variants are not clipped.
+    pub fn push_clip(&mut self, rect: Rect) {
+        let rect = match self.clips.last() {
+            Some(clip) => clip.intersect(&rect),
+            None => rect,
+        };
+        self.clips.push(rect);
+    }
+
+    pub fn pop_clip(&mut self) {
+        self.clips.pop();
+    }
+
+    fn is_visible(&self, x: usize, y: usize, width: usize) -> bool {
+        match self.clips.last() {
+            Some(clip) => clip.contains(x, y) && clip.contains(x + width - 1, y),
+            None => true,
         }
     }
 
-    pub fn write(&mut self, chars: &str) {
+    fn set_cell(&mut self, x: usize, y: usize, c: Character) {
+        let width = grapheme_width(&c.grapheme);
+        if !self.is_visible(x, y, width) {
+            return;
+        }
+
+        if self.characters.len() <= y {
+            self.characters.resize_with(y + 1, Vec::new);
+        }
+        let line = &mut self.characters[y];
+        if line.len() < x + width {
+            line.resize_with(x + width, Character::padding);
+        }
+
+        // Double-width graphemes which are partly overwritten are replaced with blanks
+        if line[x].is_continuation() && x > 0 {
+            line[x - 1] = Character::new(" ", line[x - 1].style);
+        }
+        if line.get(x + width).is_some_and(|x| x.is_continuation()) {
+            line[x + width] = Character::new(" ", line[x + width].style);
+        }
+
+        let style = c.style;
+        line[x] = c;
+        for cell in &mut line[x + 1..x + width] {
+            *cell = Character::new("", style);
+        }
+    }
+
+    pub fn put(&mut self, x: usize, y: usize, text: &str, style: Style) {
+        let mut x = x;
+        for grapheme in text.graphemes(true) {
+            self.set_cell(x, y, Character::new(grapheme, style));
+            x += grapheme_width(grapheme);
+        }
+    }
+
+    pub fn fill_rect(&mut self, rect: Rect, c: char, style: Style) {
+        let text: String = std::iter::repeat_n(c, rect.width).collect();
+        for y in rect.y..rect.y + rect.height {
+            self.put(rect.x, y, &text, style);
+        }
+    }
+
+    // Only the border is drawn, with line-drawing characters
+    pub fn draw_box(&mut self, rect: Rect, style: Style) {
+        if rect.width < 2 || rect.height < 2 {
+            return;
+        }
+
+        let right = rect.x + rect.width - 1;
+        let bottom = rect.y + rect.height - 1;
+        let horizontal: String = std::iter::repeat_n('─', rect.width - 2).collect();
+
+        self.put(rect.x, rect.y, &format!("┌{}┐", horizontal), style);
+        for y in rect.y + 1..bottom {
+            self.put(rect.x, y, "│", style);
+            self.put(right, y, "│", style);
+        }
+        self.put(rect.x, bottom, &format!("└{}┘", horizontal), style);
+    }
+
+    // Draws `layer` on top with its origin at the position. Cells the layer never drew
+    // on are transparent.
+    pub fn overlay(&mut self, layer: &ScreenBuffer, x: usize, y: usize) {
+        for (dy, line) in layer.characters.iter().enumerate() {
+            for (dx, c) in line.iter().enumerate() {
+                if !c.is_padding && !c.is_continuation() {
+                    self.set_cell(x + dx, y + dy, c.clone());
+                }
+            }
+        }
+    }
+
+    pub fn write_styled(&mut self, chars: &str, style: Style) {
         let line = self.characters.last_mut().unwrap();
 
-        for c in chars.chars() {
-            line.push(Character::new(c, None));
+        for grapheme in chars.graphemes(true) {
+            // Combining marks written separately are joined to the preceding grapheme
+            if grapheme.width() == 0 {
+                if let Some(last) = line.iter_mut().rev().find(|x| !x.is_continuation()) {
+                    last.grapheme.push_str(grapheme);
+                    continue;
+                }
+            }
+
+            line.push(Character::new(grapheme, style));
+            for _ in 1..grapheme_width(grapheme) {
+                line.push(Character::new("", style));
+            }
         }
     }
 
+    pub fn write_color(&mut self, chars: &str, fg_color: Color, bg_color: Color) {
+        self.write_styled(chars, Style::new().fg(fg_color).bg(bg_color));
+    }
+
+    pub fn write(&mut self, chars: &str) {
+        self.write_styled(chars, Style::new());
+    }
+
     pub fn new_line(&mut self) {
         self.characters.push(Vec::new());
     }
 }
+
+impl Default for ScreenBuffer {
+    fn default() -> Self {
+        ScreenBuffer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    const WIDTH: usize = 30;
+    const HEIGHT: usize = 16;
+    const FRAMES: usize = 600;
+
+    // Counts the bytes while `Screen` owns the writer
+    #[derive(Clone, Default)]
+    struct Counter(Rc<Cell<usize>>);
+
+    impl Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.set(self.0.get() + buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // (character, fg SGR code, bg SGR code, reverse) per cell
+    type Frame = Vec<Vec<(char, usize, usize, bool)>>;
+
+    // A Minesweeper-like board with cells revealed over time, a moving cursor and a timer
+    fn frame(index: usize) -> Frame {
+        let revealed = index / 10;
+        let cursor = (index / 3 % WIDTH, index / 7 % HEIGHT);
+        let timer = format!(" {:0>3} ", index / 60);
+
+        let mut lines = vec![timer.chars().map(|c| (c, 31, 47, false)).collect()];
+        for y in 0..HEIGHT {
+            let mut line = Vec::new();
+            for x in 0..WIDTH {
+                let is_revealed = y * WIDTH + x < revealed;
+                let (text, fg, bg) = if is_revealed {
+                    ([' ', ' ', (b'1' + (x * y % 3) as u8) as char], 34, 47)
+                } else {
+                    (['[', ' ', ']'], 37, 40)
+                };
+                line.extend(text.map(|c| (c, fg, bg, (x, y) == cursor)));
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    fn named(code: usize) -> Color {
+        [
+            Color::Black,
+            Color::Red,
+            Color::Green,
+            Color::Yellow,
+            Color::Blue,
+            Color::Magenta,
+            Color::Cyan,
+            Color::White,
+        ][code % 10]
+    }
+
+    fn to_buffer(lines: &Frame) -> ScreenBuffer {
+        let mut buffer = ScreenBuffer::new();
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                buffer.new_line();
+            }
+            for &(c, fg, bg, reverse) in line {
+                let mut style = Style::new().fg(named(fg)).bg(named(bg));
+                if reverse {
+                    style = style.reverse();
+                }
+                buffer.write_styled(&c.to_string(), style);
+            }
+        }
+        buffer
+    }
+
+    // The bytes of moving the cursor to each changed cell and writing its own SGR sequence
+    fn per_cell_bytes(old: Option<&Frame>, new: &Frame) -> usize {
+        let mut bytes = 0;
+        for (y, line) in new.iter().enumerate() {
+            for (x, &(c, fg, bg, reverse)) in line.iter().enumerate() {
+                let old_cell = old.and_then(|old| old.get(y)?.get(x));
+                if old_cell != Some(&(c, fg, bg, reverse)) {
+                    let reverse = if reverse { "7;" } else { "" };
+                    bytes += format!(
+                        "\x1b[{};{}H\x1b[{}{};{}m{}\x1b[0m",
+                        y + 1,
+                        x + 1,
+                        reverse,
+                        fg,
+                        bg,
+                        c
+                    )
+                    .len();
+                }
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn render_writes_less_than_per_cell_updates() {
+        let counter = Counter::default();
+        let mut screen = Screen::new(counter.clone());
+        let mut previous = None;
+        let mut per_cell = 0;
+
+        for index in 0..FRAMES {
+            let lines = frame(index);
+            per_cell += per_cell_bytes(previous.as_ref(), &lines);
+            screen.render(to_buffer(&lines)).unwrap();
+            previous = Some(lines);
+        }
+
+        let written = counter.0.get();
+        assert!(
+            written < per_cell,
+            "Screen wrote {} bytes, per-cell updates take {}",
+            written,
+            per_cell
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use crate::color::ColorSupport;
+use crate::game::{Event, EventSender};
+use std::io::{Read, Write};
+
+const SE: u8 = 240;
+const SB: u8 = 250;
+const WILL: u8 = 251;
+const WONT: u8 = 252;
+const DO: u8 = 253;
+const DONT: u8 = 254;
+const IAC: u8 = 255;
+
+const ECHO: u8 = 1;
+const SGA: u8 = 3;
+const TTYPE: u8 = 24;
+const NAWS: u8 = 31;
+const LINEMODE: u8 = 34;
+
+const TTYPE_IS: u8 = 0;
+const TTYPE_SEND: u8 = 1;
+const LINEMODE_MODE: u8 = 1;
+
+// Longer subnegotiations are dropped. NAWS and TTYPE take far less.
+const MAX_SUBNEGOTIATION_LENGTH: usize = 64;
+
+// Options the server performs itself, and options it asks the client to perform
+const LOCAL_OPTIONS: [u8; 2] = [ECHO, SGA];
+const REMOTE_OPTIONS: [u8; 4] = [SGA, TTYPE, NAWS, LINEMODE];
+
+#[derive(Clone, Copy, PartialEq)]
+enum OptionState {
+    Disabled,
+    Requested,
+    Enabled,
+}
+
+#[derive(Clone, Copy)]
+enum ParseState {
+    None,
+    WaitCommand,
+    WaitOption(u8),
+    Subnegotiation,
+    SubnegotiationCommand,
+}
+
+pub struct TelnetStream<S: Read + Write> {
+    state: ParseState,
+    subnegotiation: Vec<u8>,
+    local: [OptionState; 256],
+    remote: [OptionState; 256],
+    events: EventSender,
+    stream: S,
+}
+
+impl<S: Read + Write> TelnetStream<S> {
+    pub fn new(stream: S, events: EventSender) -> Self {
+        TelnetStream {
+            state: ParseState::None,
+            subnegotiation: Vec::new(),
+            local: [OptionState::Disabled; 256],
+            remote: [OptionState::Disabled; 256],
+            events,
+            stream,
+        }
+    }
+
+    pub fn negotiate(&mut self) -> std::io::Result<()> {
+        for option in LOCAL_OPTIONS {
+            self.local[option as usize] = OptionState::Requested;
+            self.stream.write_all(&[IAC, WILL, option])?;
+        }
+
+        for option in REMOTE_OPTIONS {
+            self.remote[option as usize] = OptionState::Requested;
+            self.stream.write_all(&[IAC, DO, option])?;
+        }
+
+        self.stream.flush()
+    }
+
+    fn enable_remote(&mut self, option: u8) -> std::io::Result<()> {
+        self.remote[option as usize] = OptionState::Enabled;
+
+        match option {
+            TTYPE => self
+                .stream
+                .write_all(&[IAC, SB, TTYPE, TTYPE_SEND, IAC, SE]),
+            LINEMODE => self
+                .stream
+                .write_all(&[IAC, SB, LINEMODE, LINEMODE_MODE, 0, IAC, SE]),
+            _ => Ok(()),
+        }
+    }
+
+    fn process_option(&mut self, command: u8, option: u8) -> std::io::Result<()> {
+        let index = option as usize;
+
+        match command {
+            WILL if !REMOTE_OPTIONS.contains(&option) => {
+                self.stream.write_all(&[IAC, DONT, option])?;
+            }
+            WILL => match self.remote[index] {
+                OptionState::Disabled => {
+                    self.stream.write_all(&[IAC, DO, option])?;
+                    self.enable_remote(option)?;
+                }
+                OptionState::Requested => self.enable_remote(option)?,
+                OptionState::Enabled => (),
+            },
+            WONT => {
+                if self.remote[index] == OptionState::Enabled {
+                    self.stream.write_all(&[IAC, DONT, option])?;
+                }
+                self.remote[index] = OptionState::Disabled;
+            }
+            DO if !LOCAL_OPTIONS.contains(&option) => {
+                self.stream.write_all(&[IAC, WONT, option])?;
+            }
+            DO => {
+                if self.local[index] == OptionState::Disabled {
+                    self.stream.write_all(&[IAC, WILL, option])?;
+                }
+                self.local[index] = OptionState::Enabled;
+            }
+            DONT => {
+                if self.local[index] == OptionState::Enabled {
+                    self.stream.write_all(&[IAC, WONT, option])?;
+                }
+                self.local[index] = OptionState::Disabled;
+            }
+            _ => (),
+        }
+
+        self.stream.flush()
+    }
+
+    // One byte past the limit is kept to tell that it was exceeded
+    fn push_subnegotiation(&mut self, byte: u8) {
+        if self.subnegotiation.len() <= MAX_SUBNEGOTIATION_LENGTH {
+            self.subnegotiation.push(byte);
+        }
+    }
+
+    fn process_subnegotiation(&mut self) {
+        if self.subnegotiation.len() > MAX_SUBNEGOTIATION_LENGTH {
+            self.subnegotiation.clear();
+            return;
+        }
+
+        let event = match self.subnegotiation[..] {
+            [NAWS, w1, w2, h1, h2] => {
+                let width = u16::from_be_bytes([w1, w2]) as usize;
+                let height = u16::from_be_bytes([h1, h2]) as usize;
+                Some(Event::Resize(width, height)).filter(|_| width > 0 && height > 0)
+            }
+            [TTYPE, TTYPE_IS, ref name @ ..] => {
+                let name = String::from_utf8_lossy(name).to_ascii_lowercase();
+                let color_support = ColorSupport::from_terminal_type(&name);
+                let _ = self.events.send(Ok(Event::ColorSupport(color_support)));
+                Some(Event::TerminalType(name))
+            }
+            _ => None,
+        };
+
+        if let Some(event) = event {
+            let _ = self.events.send(Ok(event));
+        }
+
+        self.subnegotiation.clear();
+    }
+
+    // Returns the byte if it is a part of the data stream
+    fn process_byte(&mut self, byte: u8) -> std::io::Result<Option<u8>> {
+        let (next_state, result) = match (self.state, byte) {
+            (ParseState::None, IAC) => (ParseState::WaitCommand, None),
+            (ParseState::None, _) => (ParseState::None, Some(byte)),
+            (ParseState::WaitCommand, IAC) => (ParseState::None, Some(IAC)),
+            (ParseState::WaitCommand, SB) => (ParseState::Subnegotiation, None),
+            (ParseState::WaitCommand, WILL..=DONT) => (ParseState::WaitOption(byte), None),
+            (ParseState::WaitCommand, _) => (ParseState::None, None),
+            (ParseState::WaitOption(command), _) => {
+                self.process_option(command, byte)?;
+                (ParseState::None, None)
+            }
+            (ParseState::Subnegotiation, IAC) => (ParseState::SubnegotiationCommand, None),
+            (ParseState::Subnegotiation, _) => {
+                self.push_subnegotiation(byte);
+                (ParseState::Subnegotiation, None)
+            }
+            (ParseState::SubnegotiationCommand, IAC) => {
+                self.push_subnegotiation(byte);
+                (ParseState::Subnegotiation, None)
+            }
+            (ParseState::SubnegotiationCommand, SE) => {
+                self.process_subnegotiation();
+                (ParseState::None, None)
+            }
+            (ParseState::SubnegotiationCommand, _) => (ParseState::Subnegotiation, None),
+        };
+
+        self.state = next_state;
+        Ok(result)
+    }
+}
+
+impl<S: Read + Write> Read for TelnetStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let size = self.stream.read(buf)?;
+            if size == 0 {
+                return Ok(0);
+            }
+
+            let mut ptr = 0;
+            for i in 0..size {
+                if let Some(byte) = self.process_byte(buf[i])? {
+                    buf[ptr] = byte;
+                    ptr += 1;
+                }
+            }
+
+            // Returning 0 would be taken as the end of the stream
+            if ptr > 0 {
+                return Ok(ptr);
+            }
+        }
+    }
+}
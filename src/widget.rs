@@ -0,0 +1,419 @@
+use crate::key::Key;
+use crate::screen::{display_width, Rect, ScreenBuffer, Style};
+
+pub trait Widget {
+    fn render(&self, screen: &mut ScreenBuffer, rect: Rect);
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+// Text which doesn't fit in the width is cut off
+fn put_aligned(
+    screen: &mut ScreenBuffer,
+    x: usize,
+    y: usize,
+    width: usize,
+    text: &str,
+    align: Align,
+    style: Style,
+) {
+    let space = width.saturating_sub(display_width(text));
+    let offset = match align {
+        Align::Left => 0,
+        Align::Center => space / 2,
+        Align::Right => space,
+    };
+
+    screen.push_clip(Rect::new(x, y, width, 1));
+    screen.put(x + offset, y, text, style);
+    screen.pop_clip();
+}
+
+pub struct Label {
+    text: String,
+    align: Align,
+    style: Style,
+}
+
+impl Label {
+    pub fn new(text: &str) -> Self {
+        Label {
+            text: text.to_string(),
+            align: Align::Left,
+            style: Style::new(),
+        }
+    }
+
+    pub fn align(self, align: Align) -> Self {
+        Label { align, ..self }
+    }
+
+    pub fn style(self, style: Style) -> Self {
+        Label { style, ..self }
+    }
+}
+
+impl Widget for Label {
+    fn render(&self, screen: &mut ScreenBuffer, rect: Rect) {
+        for (i, line) in self.text.lines().take(rect.height).enumerate() {
+            put_aligned(
+                screen,
+                rect.x,
+                rect.y + i,
+                rect.width,
+                line,
+                self.align,
+                self.style,
+            );
+        }
+    }
+}
+
+pub struct Panel {
+    title: Option<String>,
+    style: Style,
+}
+
+impl Panel {
+    pub fn new() -> Self {
+        Panel {
+            title: None,
+            style: Style::new(),
+        }
+    }
+
+    pub fn title(self, title: &str) -> Self {
+        Panel {
+            title: Some(title.to_string()),
+            ..self
+        }
+    }
+
+    pub fn style(self, style: Style) -> Self {
+        Panel { style, ..self }
+    }
+
+    // The area inside the border
+    pub fn inner(&self, rect: Rect) -> Rect {
+        Rect::new(
+            rect.x + 1,
+            rect.y + 1,
+            rect.width.saturating_sub(2),
+            rect.height.saturating_sub(2),
+        )
+    }
+}
+
+impl Default for Panel {
+    fn default() -> Self {
+        Panel::new()
+    }
+}
+
+impl Widget for Panel {
+    fn render(&self, screen: &mut ScreenBuffer, rect: Rect) {
+        screen.fill_rect(rect, ' ', self.style);
+        screen.draw_box(rect, self.style);
+
+        if let Some(title) = &self.title {
+            let width = rect.width.saturating_sub(4);
+            put_aligned(
+                screen,
+                rect.x + 2,
+                rect.y,
+                width,
+                &format!(" {} ", title),
+                Align::Left,
+                self.style.bold(),
+            );
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum MenuAction {
+    None,
+    Select(usize),
+    Cancel,
+}
+
+pub struct Menu {
+    items: Vec<String>,
+    selected: usize,
+    style: Style,
+}
+
+impl Menu {
+    pub fn new(items: &[&str]) -> Self {
+        Menu {
+            items: items.iter().map(|x| x.to_string()).collect(),
+            selected: 0,
+            style: Style::new(),
+        }
+    }
+
+    pub fn style(self, style: Style) -> Self {
+        Menu { style, ..self }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn set_selected(&mut self, selected: usize) {
+        self.selected = selected.min(self.items.len().saturating_sub(1));
+    }
+
+    pub fn process_key(&mut self, key: &Key) -> MenuAction {
+        let count = self.items.len();
+        if count == 0 {
+            return MenuAction::None;
+        }
+
+        match key {
+            Key::ArrowUp | Key::Character('k') => {
+                self.selected = (self.selected + count - 1) % count
+            }
+            Key::ArrowDown | Key::Character('j') | Key::Tab => {
+                self.selected = (self.selected + 1) % count
+            }
+            Key::Home | Key::PageUp => self.selected = 0,
+            Key::End | Key::PageDown => self.selected = count - 1,
+            Key::Return | Key::Character(' ') => return MenuAction::Select(self.selected),
+            Key::Escape => return MenuAction::Cancel,
+            _ => (),
+        }
+
+        MenuAction::None
+    }
+}
+
+impl Widget for Menu {
+    // Scrolls so that the selected item is always shown
+    fn render(&self, screen: &mut ScreenBuffer, rect: Rect) {
+        let first = (self.selected + 1).saturating_sub(rect.height);
+
+        for (i, item) in self.items.iter().enumerate().skip(first).take(rect.height) {
+            let (marker, style) = if i == self.selected {
+                ("> ", self.style.reverse())
+            } else {
+                ("  ", self.style)
+            };
+            put_aligned(
+                screen,
+                rect.x,
+                rect.y + i - first,
+                rect.width,
+                &format!("{}{}", marker, item),
+                Align::Left,
+                style,
+            );
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum InputAction {
+    None,
+    Submit,
+    Cancel,
+}
+
+pub struct TextInput {
+    value: String,
+    max_length: usize,
+    filter: fn(char) -> bool,
+    is_focused: bool,
+    style: Style,
+}
+
+impl TextInput {
+    pub fn new(value: &str) -> Self {
+        TextInput {
+            value: value.to_string(),
+            max_length: usize::MAX,
+            filter: |c| !c.is_control(),
+            is_focused: true,
+            style: Style::new(),
+        }
+    }
+
+    pub fn max_length(self, max_length: usize) -> Self {
+        TextInput { max_length, ..self }
+    }
+
+    // Characters the filter rejects are ignored, also in pasted text
+    pub fn filter(self, filter: fn(char) -> bool) -> Self {
+        TextInput { filter, ..self }
+    }
+
+    pub fn style(self, style: Style) -> Self {
+        TextInput { style, ..self }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn set_focused(&mut self, is_focused: bool) {
+        self.is_focused = is_focused;
+    }
+
+    fn insert(&mut self, text: &str) {
+        let length = self.value.chars().count();
+        let accepted = text.chars().filter(|&c| (self.filter)(c));
+        self.value
+            .extend(accepted.take(self.max_length.saturating_sub(length)));
+    }
+
+    pub fn process_key(&mut self, key: &Key) -> InputAction {
+        match key {
+            Key::Character(c) => self.insert(&c.to_string()),
+            Key::Paste(text) => self.insert(text),
+            Key::Backspace | Key::Control('H') => {
+                self.value.pop();
+            }
+            Key::Return => return InputAction::Submit,
+            Key::Escape => return InputAction::Cancel,
+            _ => (),
+        }
+
+        InputAction::None
+    }
+}
+
+impl Widget for TextInput {
+    // The end of the value is shown when it is longer than the width
+    fn render(&self, screen: &mut ScreenBuffer, rect: Rect) {
+        let cursor = if self.is_focused { "_" } else { "" };
+        let mut text = format!("{}{}", self.value, cursor);
+        while display_width(&text) > rect.width && !text.is_empty() {
+            text.remove(0);
+        }
+
+        screen.fill_rect(Rect::new(rect.x, rect.y, rect.width, 1), ' ', self.style);
+        put_aligned(
+            screen,
+            rect.x,
+            rect.y,
+            rect.width,
+            &text,
+            Align::Left,
+            self.style,
+        );
+    }
+}
+
+pub struct ProgressBar {
+    ratio: f64,
+    style: Style,
+}
+
+impl ProgressBar {
+    pub fn new(ratio: f64) -> Self {
+        ProgressBar {
+            ratio: ratio.clamp(0.0, 1.0),
+            style: Style::new(),
+        }
+    }
+
+    pub fn style(self, style: Style) -> Self {
+        ProgressBar { style, ..self }
+    }
+}
+
+impl Widget for ProgressBar {
+    fn render(&self, screen: &mut ScreenBuffer, rect: Rect) {
+        let filled = (self.ratio * rect.width as f64).round() as usize;
+        let bar: String = (0..rect.width)
+            .map(|i| if i < filled { '█' } else { '░' })
+            .collect();
+        screen.put(rect.x, rect.y, &bar, self.style);
+    }
+}
+
+const COLUMN_GAP: usize = 2;
+
+pub struct Table {
+    headers: Vec<String>,
+    aligns: Vec<Align>,
+    rows: Vec<Vec<String>>,
+    style: Style,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Self {
+        Table {
+            headers: headers.iter().map(|x| x.to_string()).collect(),
+            aligns: vec![Align::Left; headers.len()],
+            rows: Vec::new(),
+            style: Style::new(),
+        }
+    }
+
+    pub fn align(mut self, column: usize, align: Align) -> Self {
+        self.aligns[column] = align;
+        self
+    }
+
+    pub fn style(self, style: Style) -> Self {
+        Table { style, ..self }
+    }
+
+    pub fn push_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    // The number of columns it takes when nothing is cut off
+    pub fn width(&self) -> usize {
+        let widths = self.column_widths();
+        widths.iter().sum::<usize>() + COLUMN_GAP * widths.len().saturating_sub(1)
+    }
+
+    // The number of lines it takes, including the header
+    pub fn height(&self) -> usize {
+        self.rows.len() + 1
+    }
+
+    fn column_widths(&self) -> Vec<usize> {
+        (0..self.headers.len())
+            .map(|i| {
+                self.rows
+                    .iter()
+                    .filter_map(|row| row.get(i))
+                    .chain([&self.headers[i]])
+                    .map(|x| display_width(x))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+}
+
+impl Widget for Table {
+    fn render(&self, screen: &mut ScreenBuffer, rect: Rect) {
+        let widths = self.column_widths();
+        let rows = [&self.headers].into_iter().chain(&self.rows);
+
+        screen.push_clip(rect);
+        for (y, row) in rows.enumerate().take(rect.height) {
+            let style = if y == 0 {
+                self.style.bold()
+            } else {
+                self.style
+            };
+
+            let mut x = rect.x;
+            for ((cell, width), align) in row.iter().zip(&widths).zip(&self.aligns) {
+                put_aligned(screen, x, rect.y + y, *width, cell, *align, style);
+                x += width + COLUMN_GAP;
+            }
+        }
+        screen.pop_clip();
+    }
+}